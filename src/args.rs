@@ -1,5 +1,9 @@
+use crate::outln;
 use crate::report::{ReportConfig, ReportKind, ReportLevel};
+use crate::vm::VmOptions;
 use std::fmt::{Debug, Display, Formatter};
+use std::num::IntErrorKind;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::sync::LazyLock;
 
@@ -15,11 +19,27 @@ impl ReportKind for ArgParserReport {
     }
 }
 
-pub static ARGS: LazyLock<Args> = LazyLock::new(|| Args::parse(std::env::args().skip(1).collect()));
+/// How much `--trace` prints while the VM runs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Trace {
+    /// Every instruction, disassembled, on stdout.
+    Instructions,
+    /// The source of each statement as it starts, on stderr.
+    Statements,
+}
+
+pub static ARGS: LazyLock<Args> = LazyLock::new(|| {
+    // The test harness is run with arguments of its own.
+    if cfg!(test) {
+        Args::default()
+    } else {
+        Args::parse(std::env::args().skip(1).collect())
+    }
+});
 
 macro_rules! error {
     ($($ident:tt)*) => {
-        ArgParserReport(format!($($ident)*)).make().finish().write(std::io::stderr(), ReportConfig { compact: false, context: false });
+        $crate::output::check(ArgParserReport(format!($($ident)*)).make().finish().write(std::io::stderr(), ReportConfig { compact: false, context: false }));
         exit(1);
     };
 }
@@ -46,6 +66,13 @@ impl<T: Copy + Clone> Arg<T> {
         self.value = value;
     }
 
+    /// Replaces the default value, unless one was given on the command line.
+    fn preset(&mut self, value: T) {
+        if !self.set {
+            self.value = value;
+        }
+    }
+
     pub fn to_value(self) -> T {
         self.value
     }
@@ -72,15 +99,18 @@ macro_rules! arg_struct {
             )+
         }
 
-        #[allow(dead_code)]
-        impl $name {
-            pub fn default() -> Self {
+        impl Default for $name {
+            fn default() -> Self {
                 Self {
                     $(
                     $field:Arg::new($field_default),
                     )+
                 }
             }
+        }
+
+        #[allow(dead_code)]
+        impl $name {
             $(
             pub fn $field(&self) -> $field_type {
                 self.$field.to_value()
@@ -97,10 +127,49 @@ arg_struct!(
         compact: bool=(false),
         context: bool=(true),
         max_reports: usize=(usize::MAX),
-        trace_execution: bool=(false),
+        trace: Option<Trace>=(None),
         show_bytecode: bool=(false),
+        time: bool=(false),
+        profile: bool=(false),
+        sandbox: bool=(false),
+        max_instructions: Option<usize>=(None),
+        stack_size: usize=(1 << 16),
+        max_string_length: usize=(1 << 30),
+        import_root: Option<&'static str>=(None),
 });
 
+/// Parses the NUMBER given to the flag `arg`, exiting with a message that
+/// quotes the input if it isn't a non-negative integer that fits a usize.
+fn parse_number(arg: &str, value: Option<String>) -> usize {
+    let Some(value) = value else {
+        error!("{} expected NUMBER", arg);
+    };
+    match number(arg, &value) {
+        Ok(value) => value,
+        Err(message) => {
+            error!("{}", message);
+        }
+    }
+}
+
+/// `value` as a NUMBER for `arg`, or the message to exit with.
+fn number(arg: &str, value: &str) -> Result<usize, String> {
+    value.parse::<usize>().map_err(|e| match e.kind() {
+        IntErrorKind::PosOverflow => {
+            format!(
+                "'{}' is too large for {} (at most {})",
+                value,
+                arg,
+                usize::MAX
+            )
+        }
+        _ if value.starts_with('-') => {
+            format!("{} expected a NUMBER of at least 0, got '{}'", arg, value)
+        }
+        _ => format!("'{}' is not a valid NUMBER for {}", value, arg),
+    })
+}
+
 impl Args {
     fn handle_arg(
         &mut self,
@@ -126,39 +195,19 @@ impl Args {
             }
             match arg.as_str() {
                 "-h" => {
-                    let usage = format!(
-                        "{} {}",
-                        std::env::current_exe()
-                            .unwrap()
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .unwrap(),
-                        USAGE
-                    );
-                    println!("Usage: {}", usage);
+                    outln!("Usage: {}", usage());
                     exit(0);
                 }
                 "--help" => {
-                    let usage = format!(
-                        "{} {}",
-                        std::env::current_exe()
-                            .unwrap()
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .unwrap(),
-                        USAGE
-                    );
-                    println!("\x1b[1mUSAGE\x1b[0m\n{}\n\n{}", usage, HELP_MESSAGE);
+                    outln!("\x1b[1mUSAGE\x1b[0m\n{}\n\n{}", usage(), HELP_MESSAGE);
                     exit(0);
                 }
                 "-V" | "--version" => {
-                    println!("ruse {}", env!("CARGO_PKG_VERSION"));
+                    outln!("ruse {}", env!("CARGO_PKG_VERSION"));
                     exit(0);
                 }
                 "-L" | "--license" => {
-                    println!("{LICENSE}");
+                    outln!("{LICENSE}");
                     exit(0);
                 }
                 "-l" | "--report-level" => {
@@ -185,19 +234,37 @@ impl Args {
                     self.context.try_mut(arg, false);
                 }
                 "--max-reports" => {
+                    let value = parse_number(&arg, arguments.next());
+                    self.max_reports.try_mut(arg, value);
+                }
+                "--max-string-length" => {
+                    let value = parse_number(&arg, arguments.next());
+                    self.max_string_length.try_mut(arg, value);
+                }
+                "--import-root" => {
                     let Some(value) = arguments.next() else {
-                        error!("{} expected NUMBER", arg);
+                        error!("{} expected DIR", arg);
                     };
-                    let value = match value.parse::<usize>() {
-                        Ok(value) => value,
-                        Err(e) => {
-                            error!("'{}' is not a valid NUMBER", e);
+                    self.import_root.try_mut(arg, Some(value.leak()));
+                }
+                "--trace" => {
+                    let Some(value) = arguments.next() else {
+                        error!("{} expected GRANULARITY", arg);
+                    };
+                    let trace = match value.as_str() {
+                        "instructions" => Trace::Instructions,
+                        "statements" => Trace::Statements,
+                        _ => {
+                            error!("'{}' is not a valid GRANULARITY", value);
                         }
                     };
-                    self.max_reports.try_mut(arg, value);
+                    self.trace.try_mut(arg, Some(trace));
                 }
-                "--trace-execution" => self.trace_execution.try_mut(arg, true),
+                "--trace-execution" => self.trace.try_mut(arg, Some(Trace::Instructions)),
                 "--show-bytecode" => self.show_bytecode.try_mut(arg, true),
+                "--time" => self.time.try_mut(arg, true),
+                "--profile" => self.profile.try_mut(arg, true),
+                "--sandbox" => self.sandbox.try_mut(arg, true),
                 _ => {
                     error!("unrecognized argument {}", arg);
                 }
@@ -219,12 +286,42 @@ impl Args {
         if let Some(arg) = args.next() {
             error!("unexpected argument '{}'", arg);
         }
+        if out.sandbox() {
+            // Limits given explicitly win over the sandbox's, and imports
+            // stay inside the script's directory.
+            let dir = out
+                .input()
+                .and_then(|input| Path::new(input).parent())
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let sandbox = VmOptions::sandbox(dir);
+            out.max_instructions.preset(sandbox.max_instructions);
+            out.stack_size.preset(sandbox.stack_size);
+            out.max_string_length.preset(sandbox.max_string_length);
+            out.import_root
+                .preset(Some(dir.to_string_lossy().into_owned().leak()));
+        }
         out
     }
 }
 
 const LICENSE: &str = include_str!("../LICENSE");
 const USAGE: &str = "[-hVLdc] [-l level] <INPUT FILE>";
+
+/// The usage line, named after the running executable, or after the
+/// package if that can't be worked out.
+fn usage() -> String {
+    usage_for(std::env::current_exe().ok())
+}
+
+fn usage_for(exe: Option<PathBuf>) -> String {
+    let name = exe
+        .as_ref()
+        .and_then(|exe| exe.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or(env!("CARGO_PKG_NAME"));
+    format!("{} {}", name, USAGE)
+}
 const HELP_MESSAGE: &str = "\x1b[1mDESCRIPTION\x1b[0m
     Moonlite is a compiled language that runs on a custom
     bytecode VM.
@@ -240,7 +337,80 @@ const HELP_MESSAGE: &str = "\x1b[1mDESCRIPTION\x1b[0m
     -c, --compact                     Display reports in one line
 
         --disable-context             Disable the code context in reports
-        --max-reports                 Set a maximum amount of reports to be printed
-        --trace-execution             Show each instruction as it is executed
+        --max-reports NUMBER          Set a maximum amount of reports to be printed
+                                      (0 prints only the summary)
+        --max-string-length NUMBER    Stop with an error when a string would grow past NUMBER bytes
+       (default: 1073741824)
+        --trace GRANULARITY           Show each instruction or statement as it is executed
+                                      [instructions|statements]
+        --trace-execution             Same as --trace instructions
         --show-bytecode               Display the compiled bytecode
+        --time                        Show time and memory statistics for each phase
+        --profile                     Show how often each opcode and source line ran
+        --import-root DIR             Only allow importing modules from inside DIR
+        --sandbox                     Limit untrusted scripts to 100000000 instructions,
+                                      4096 stack values, strings of 1048576 bytes and
+                                      imports from their own directory, unless
+                                      --max-string-length or --import-root is set
 ";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number("--max-reports", "0"), Ok(0));
+        assert_eq!(number("--max-reports", "12"), Ok(12));
+        assert_eq!(
+            number("--max-reports", &usize::MAX.to_string()),
+            Ok(usize::MAX)
+        );
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert_eq!(
+            number("--max-reports", "-1"),
+            Err("--max-reports expected a NUMBER of at least 0, got '-1'".to_string())
+        );
+    }
+
+    #[test]
+    fn huge_numbers() {
+        let huge = format!("{}0", usize::MAX);
+        assert_eq!(
+            number("--max-string-length", &huge),
+            Err(format!(
+                "'{huge}' is too large for --max-string-length (at most {})",
+                usize::MAX
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for value in ["", "ten", "1.5", "0x10"] {
+            assert_eq!(
+                number("--max-reports", value),
+                Err(format!("'{value}' is not a valid NUMBER for --max-reports"))
+            );
+        }
+    }
+
+    #[test]
+    fn usage_names_the_executable() {
+        let exe = PathBuf::from("/usr/local/bin/ml");
+        assert_eq!(usage_for(Some(exe)), format!("ml {USAGE}"));
+    }
+
+    #[test]
+    fn usage_without_an_executable() {
+        // As when the executable has been deleted since it started.
+        assert_eq!(usage_for(None), format!("moonlite {USAGE}"));
+        assert_eq!(
+            usage_for(Some(PathBuf::from("/"))),
+            format!("moonlite {USAGE}")
+        );
+    }
+}
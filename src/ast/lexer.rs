@@ -156,10 +156,25 @@ impl<'contents> Lexer<'contents> {
                     }
                     let kind = match self.slice(start, self.current_index) {
                         "True" | "False" => TokenKind::BooleanLiteral,
+                        "None" => TokenKind::NoneLiteral,
                         "let" => TokenKind::Let,
                         "return" => TokenKind::Return,
+                        "if" => TokenKind::If,
+                        "else" => TokenKind::Else,
+                        "elif" => TokenKind::Elif,
+                        "then" => TokenKind::Then,
+                        "while" => TokenKind::While,
+                        "loop" => TokenKind::Loop,
+                        "do" => TokenKind::Do,
+                        "break" => TokenKind::Break,
+                        "for" => TokenKind::For,
+                        "in" => TokenKind::In,
+                        "match" => TokenKind::Match,
+                        "import" => TokenKind::Import,
+                        "as" => TokenKind::As,
                         "and" => TokenKind::And,
                         "or" => TokenKind::Or,
+                        "not" => TokenKind::Not,
                         _ => TokenKind::Identifier,
                     };
                     self.make_simple(start, kind)
@@ -179,7 +194,7 @@ impl<'contents> Lexer<'contents> {
                 '0'..='9' => {
                     self.lex_integer(start, Base::Decimal)?;
                     if let Some('.') = self.current_char {
-                        if !self.peek_char().is_some_and(|c| c == &'.') {
+                        if self.peek_char().is_none_or(|c| c != &'.') {
                             self.advance();
                             self.lex_integer(start, Base::Decimal)?;
                         }
@@ -200,13 +215,14 @@ impl<'contents> Lexer<'contents> {
                         self.slice(start + 1, self.current_index - 1),
                     )
                 }
-                '/' => match self.peek_char() {
-                    Some('/') => {
-                        while self.current_char.is_some_and(|c| c != '\n') {
-                            self.advance();
-                        }
-                        continue;
+                '#' => {
+                    while self.current_char.is_some_and(|c| c != '\n') {
+                        self.advance();
                     }
+                    continue;
+                }
+                '/' => match self.peek_char() {
+                    Some('/') => self.make_advance(start, 2, TokenKind::SlashSlash),
                     Some('*') => {
                         let mut depth = 0;
                         while let Some(c) = self.current_char {
@@ -240,27 +256,63 @@ impl<'contents> Lexer<'contents> {
                         }
                         continue;
                     }
+                    Some('=') => self.make_advance(start, 2, TokenKind::SlashEquals),
                     _ => self.make_advance(start, 1, TokenKind::Slash),
                 },
-                '+' => self.make_advance(start, 1, TokenKind::Plus),
-                '-' => self.make_advance(start, 1, TokenKind::Minus),
-                '*' => self.make_advance(start, 1, TokenKind::Star),
+                '+' => match self.peek_char() {
+                    Some('=') => self.make_advance(start, 2, TokenKind::PlusEquals),
+                    _ => self.make_advance(start, 1, TokenKind::Plus),
+                },
+                '-' => match self.peek_char() {
+                    Some('=') => self.make_advance(start, 2, TokenKind::MinusEquals),
+                    _ => self.make_advance(start, 1, TokenKind::Minus),
+                },
+                '*' => match self.peek_char() {
+                    Some('=') => self.make_advance(start, 2, TokenKind::StarEquals),
+                    _ => self.make_advance(start, 1, TokenKind::Star),
+                },
                 '(' => self.make_advance(start, 1, TokenKind::LeftParen),
                 ')' => self.make_advance(start, 1, TokenKind::RightParen),
+                '{' => self.make_advance(start, 1, TokenKind::LeftBrace),
+                '}' => self.make_advance(start, 1, TokenKind::RightBrace),
+                '[' => self.make_advance(start, 1, TokenKind::LeftBracket),
+                ']' => self.make_advance(start, 1, TokenKind::RightBracket),
+                ',' => self.make_advance(start, 1, TokenKind::Comma),
+                '.' => match self.peek_char() {
+                    Some('.') => {
+                        self.advance();
+                        self.advance();
+                        match self.current_char {
+                            Some('=') => self.make_advance(start, 1, TokenKind::DotDotEquals),
+                            _ => self.make_simple(start, TokenKind::DotDot),
+                        }
+                    }
+                    _ => self.make_advance(start, 1, TokenKind::Dot),
+                },
                 ';' => self.make_advance(start, 1, TokenKind::Semicolon),
                 ':' => self.make_advance(start, 1, TokenKind::Colon),
                 '=' => match self.peek_char() {
                     Some('=') => self.make_advance(start, 2, TokenKind::EqualsEquals),
+                    Some('>') => self.make_advance(start, 2, TokenKind::FatArrow),
                     _ => self.make_advance(start, 1, TokenKind::Equals),
                 },
                 '>' => match self.peek_char() {
                     Some('=') => self.make_advance(start, 2, TokenKind::GreaterThanEquals),
+                    Some('>') => self.make_advance(start, 2, TokenKind::GreaterGreater),
                     _ => self.make_advance(start, 1, TokenKind::GreaterThan),
                 },
                 '<' => match self.peek_char() {
                     Some('=') => self.make_advance(start, 2, TokenKind::LessThanEquals),
+                    Some('<') => self.make_advance(start, 2, TokenKind::LessLess),
                     _ => self.make_advance(start, 1, TokenKind::LessThan),
                 },
+                '&' => self.make_advance(start, 1, TokenKind::Ampersand),
+                '|' => self.make_advance(start, 1, TokenKind::Pipe),
+                '^' => self.make_advance(start, 1, TokenKind::Caret),
+                '?' if self.peek_char() == Some(&'?') => {
+                    self.make_advance(start, 2, TokenKind::QuestionQuestion)
+                }
+                '~' => self.make_advance(start, 1, TokenKind::Tilde),
                 '!' => match self.peek_char() {
                     Some('=') => self.make_advance(start, 2, TokenKind::BangEquals),
                     _ => self.make_advance(start, 1, TokenKind::Bang),
@@ -283,9 +335,17 @@ impl<'contents> Lexer<'contents> {
                 _ => self.advance(),
             }
         }
-        if !self.current_char.is_some_and(|c| c == closer) {
+        if self.current_char.is_none_or(|c| c != closer) {
             return Err(UnterminatedString
-                .make_labeled(self.span_from(start).label())
+                .make_labeled(
+                    self.span_from(self.current_index)
+                        .labeled(format!("Expected a closing {closer}")),
+                )
+                .with_label(
+                    self.span_at(start)
+                        .labeled("String opened here")
+                        .with_color(Color::BrightBlue),
+                )
                 .into());
         }
         self.advance();
@@ -1,66 +1,146 @@
 use crate::ast::span::Span;
 use name_variant::NamedVariant;
 use std::fmt::{Debug, Display, Formatter};
+use symbol::Symbol;
 use token::TokenKind;
 
 pub mod lexer;
 pub mod parser;
 pub mod span;
+pub mod symbol;
 pub mod token;
 
-#[derive(NamedVariant, Copy, Clone)]
+#[derive(NamedVariant, Copy, Clone, PartialEq)]
 pub enum Operator {
+    /// `a or b` is `a` if it's truthy, and `b` otherwise, which is then
+    /// never evaluated. See `Value::is_truthy`.
     Or,
+    /// `a and b` is `a` if it's falsy, and `b` otherwise, which is then
+    /// never evaluated.
     And,
     Not,
     Plus,
     Minus,
     Star,
     Slash,
+    FloorDiv,
     GreaterThan,
     LessThan,
     GreaterThanEquals,
     LessThanEquals,
     Equals,
     BangEquals,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+    In,
+    NotIn,
+    Coalesce,
 }
 
 impl Operator {
+    /// How each operator is written in source, for diagnostics and for
+    /// anything that prints code back out.
+    const SYMBOLS: [(Operator, &'static str); 23] = [
+        (Operator::Or, "or"),
+        (Operator::And, "and"),
+        (Operator::Not, "!"),
+        (Operator::Plus, "+"),
+        (Operator::Minus, "-"),
+        (Operator::Star, "*"),
+        (Operator::Slash, "/"),
+        (Operator::FloorDiv, "//"),
+        (Operator::GreaterThan, ">"),
+        (Operator::LessThan, "<"),
+        (Operator::GreaterThanEquals, ">="),
+        (Operator::LessThanEquals, "<="),
+        (Operator::Equals, "=="),
+        (Operator::BangEquals, "!="),
+        (Operator::BitAnd, "&"),
+        (Operator::BitOr, "|"),
+        (Operator::BitXor, "^"),
+        (Operator::BitNot, "~"),
+        (Operator::Shl, "<<"),
+        (Operator::Shr, ">>"),
+        (Operator::In, "in"),
+        (Operator::NotIn, "not in"),
+        (Operator::Coalesce, "??"),
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        Self::SYMBOLS
+            .iter()
+            .find(|(op, _)| op == self)
+            .map(|(_, symbol)| *symbol)
+            .expect("Operator is missing from the symbol table")
+    }
+
     pub fn is_compound(&self) -> bool {
-        match self {
+        matches!(
+            self,
             Operator::GreaterThan
-            | Operator::GreaterThanEquals
-            | Operator::LessThan
-            | Operator::LessThanEquals => true,
-            _ => false,
-        }
+                | Operator::GreaterThanEquals
+                | Operator::LessThan
+                | Operator::LessThanEquals
+        )
     }
 }
 
 impl TokenKind {
     pub fn as_prefix(self) -> Option<(Operator, (), u8)> {
         Some(match self {
-            TokenKind::Plus => (Operator::Plus, (), 1),
-            TokenKind::Minus => (Operator::Minus, (), 1),
-            TokenKind::Bang => (Operator::Not, (), 2),
+            TokenKind::Plus => (Operator::Plus, (), 13),
+            TokenKind::Minus => (Operator::Minus, (), 13),
+            TokenKind::Bang | TokenKind::Not => (Operator::Not, (), 3),
+            TokenKind::Tilde => (Operator::BitNot, (), 13),
             _ => return None,
         })
     }
 
+    /// Bitwise operators bind tighter than comparisons, unlike in C, so
+    /// `a & 1 == 0` means `(a & 1) == 0`. Ranges are parsed on their own,
+    /// between the two.
+    ///
+    /// `??` sits between `or` and `and`, so `a ?? b and c` means
+    /// `a ?? (b and c)` and `a ?? b or c` means `(a ?? b) or c`. It's left
+    /// associative, so a chain of them picks the first operand that isn't
+    /// `None`.
     pub fn as_infix(self) -> Option<(Operator, u8, u8)> {
         Some(match self {
             TokenKind::Or => (Operator::Or, 1, 2),
-            TokenKind::And => (Operator::And, 2, 3),
-            TokenKind::EqualsEquals => (Operator::Equals, 3, 4),
-            TokenKind::BangEquals => (Operator::BangEquals, 3, 4),
-            TokenKind::GreaterThan => (Operator::GreaterThan, 3, 4),
-            TokenKind::GreaterThanEquals => (Operator::GreaterThanEquals, 3, 4),
-            TokenKind::LessThan => (Operator::LessThan, 3, 4),
-            TokenKind::LessThanEquals => (Operator::LessThanEquals, 3, 4),
-            TokenKind::Plus => (Operator::Plus, 4, 5),
-            TokenKind::Minus => (Operator::Minus, 4, 5),
-            TokenKind::Star => (Operator::Star, 5, 6),
-            TokenKind::Slash => (Operator::Slash, 5, 6),
+            TokenKind::QuestionQuestion => (Operator::Coalesce, 2, 3),
+            TokenKind::And => (Operator::And, 3, 4),
+            TokenKind::EqualsEquals => (Operator::Equals, 4, 5),
+            TokenKind::BangEquals => (Operator::BangEquals, 4, 5),
+            TokenKind::GreaterThan => (Operator::GreaterThan, 4, 5),
+            TokenKind::GreaterThanEquals => (Operator::GreaterThanEquals, 4, 5),
+            TokenKind::LessThan => (Operator::LessThan, 4, 5),
+            TokenKind::LessThanEquals => (Operator::LessThanEquals, 4, 5),
+            TokenKind::In => (Operator::In, 4, 5),
+            TokenKind::Pipe => (Operator::BitOr, 6, 7),
+            TokenKind::Caret => (Operator::BitXor, 7, 8),
+            TokenKind::Ampersand => (Operator::BitAnd, 8, 9),
+            TokenKind::LessLess => (Operator::Shl, 9, 10),
+            TokenKind::GreaterGreater => (Operator::Shr, 9, 10),
+            TokenKind::Plus => (Operator::Plus, 10, 11),
+            TokenKind::Minus => (Operator::Minus, 10, 11),
+            TokenKind::Star => (Operator::Star, 11, 12),
+            TokenKind::Slash => (Operator::Slash, 11, 12),
+            TokenKind::SlashSlash => (Operator::FloorDiv, 11, 12),
+            _ => return None,
+        })
+    }
+
+    /// The operator a compound assignment like `+=` applies.
+    pub fn as_compound_assignment(self) -> Option<Operator> {
+        Some(match self {
+            TokenKind::PlusEquals => Operator::Plus,
+            TokenKind::MinusEquals => Operator::Minus,
+            TokenKind::StarEquals => Operator::Star,
+            TokenKind::SlashEquals => Operator::Slash,
             _ => return None,
         })
     }
@@ -75,16 +155,99 @@ impl TokenKind {
 
 #[derive(NamedVariant, Clone)]
 pub enum NodeKind {
-    Return(Box<Node>),
+    Return(Option<Box<Node>>),
     Block(Vec<Node>),
-    VarDeclaration(String, Box<Node>),
+    VarDeclaration(Symbol, Option<Box<Node>>),
+    /// `let a, b = xs` or `let [a, b, ..rest] = xs`, as the names bound to
+    /// each element, the name bound to a list of any elements after them,
+    /// and the list. Every pattern is a binding or `_`.
+    VarDestructure(Vec<Pattern>, Option<Pattern>, Box<Node>),
+    /// The name the module is bound to, and the path of its file.
+    Import(Symbol, &'static str),
+    /// Each condition with the block run when it's the first one true, and
+    /// the `else` block run when none are. `else if` and `elif` add to the
+    /// chain rather than nesting another `If`. Evaluates to the value of the
+    /// block run, or `None` if there's none.
+    If(Vec<(Node, Node)>, Option<Box<Node>>),
+    /// `if cond then a else b`, as the condition and the expressions it
+    /// chooses between.
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    /// The condition, and the block run for as long as it's true.
+    While(Box<Node>, Box<Node>),
+    /// A loop that only ends with a `break`, and evaluates to the value
+    /// given to it.
+    Loop(Box<Node>),
+    /// Leaves the innermost loop, with the value for a `loop` to evaluate
+    /// to.
+    Break(Option<Box<Node>>),
+    /// `do { ... }`, a block used as an expression, which evaluates to its
+    /// last statement when that's an expression, and to `None` otherwise.
+    Do(Box<Node>),
+    /// The loop variable, the range it counts over, and the block run for
+    /// each value.
+    For(Symbol, Box<Node>, Box<Node>),
+    /// The operator of a compound assignment like `+=`, the variable
+    /// assigned to, and its new value. Evaluates to the value stored, so
+    /// assignments can be chained.
+    Assignment(Option<Operator>, Box<Node>, Box<Node>),
     UnaryOperation(Operator, Box<Node>),
     BinaryOperation(Operator, Box<Node>, Box<Node>),
-    Identifier(String),
+    /// A chain of two or more comparisons like `a < b <= c`, as the first
+    /// operand and each operator with the operand to its right.
+    Comparison(Box<Node>, Vec<(Operator, Node)>),
+    /// The value matched against, and each arm's pattern with the
+    /// expression it evaluates to.
+    Match(Box<Node>, Vec<(Pattern, Node)>),
+    /// `start..end`, or `start..=end` when the flag is set.
+    Range(Box<Node>, Box<Node>, bool),
+    /// The value indexed, and the index.
+    Index(Box<Node>, Box<Node>),
+    /// `target.name`, as the target and the name.
+    MemberAccess(Box<Node>, Symbol),
+    /// The value sliced, and the bounds of the slice if they're given.
+    Slice(Box<Node>, Option<Box<Node>>, Option<Box<Node>>),
+    Identifier(Symbol),
+    ListLiteral(Vec<Node>),
+    /// Each key and its value, in the order written.
+    MapLiteral(Vec<(Node, Node)>),
     StringLiteral(String),
     FloatLiteral(f64),
-    IntegerLiteral(usize),
+    IntegerLiteral(i64),
     BooleanLiteral(bool),
+    NoneLiteral,
+}
+
+#[derive(Clone)]
+pub enum PatternKind {
+    /// Matches values equal to the literal, which may be negated.
+    Literal(Box<Node>),
+    /// Matches anything, binding it to the name within the arm.
+    Binding(Symbol),
+    /// `_`, which matches anything.
+    Wildcard,
+}
+
+#[derive(Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    /// Whether the pattern matches every value, so no later arm can run.
+    pub fn is_irrefutable(&self) -> bool {
+        !matches!(self.kind, PatternKind::Literal(_))
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            PatternKind::Literal(node) => write!(f, "{}", NodeFormatter { node, indent: 0 }),
+            PatternKind::Binding(name) => write!(f, "Binding({name:?})[{:#}]", self.span),
+            PatternKind::Wildcard => write!(f, "Wildcard[{:#}]", self.span),
+        }
+    }
 }
 
 impl NodeKind {
@@ -99,6 +262,72 @@ pub struct Node {
     pub span: Span,
 }
 
+impl Node {
+    pub fn count(&self) -> usize {
+        1 + match &self.kind {
+            NodeKind::Return(expr) => expr.as_ref().map_or(0, |expr| expr.count()),
+            NodeKind::Block(stmts) => stmts.iter().map(Node::count).sum(),
+            NodeKind::VarDeclaration(_, expr) => expr.as_ref().map_or(0, |expr| expr.count()),
+            NodeKind::VarDestructure(targets, rest, value) => {
+                targets.len() + rest.iter().count() + value.count()
+            }
+            NodeKind::If(branches, otherwise) => {
+                branches
+                    .iter()
+                    .map(|(cond, then)| cond.count() + then.count())
+                    .sum::<usize>()
+                    + otherwise.as_ref().map_or(0, |node| node.count())
+            }
+            NodeKind::Conditional(cond, then, otherwise) => {
+                cond.count() + then.count() + otherwise.count()
+            }
+            NodeKind::While(cond, body) => cond.count() + body.count(),
+            NodeKind::Loop(body) | NodeKind::Do(body) => body.count(),
+            NodeKind::Break(value) => value.as_ref().map_or(0, |value| value.count()),
+            NodeKind::For(_, range, body) => range.count() + body.count(),
+            NodeKind::Range(start, end, _) => start.count() + end.count(),
+            NodeKind::Assignment(_, target, value) => target.count() + value.count(),
+            NodeKind::UnaryOperation(_, expr) => expr.count(),
+            NodeKind::BinaryOperation(_, lhs, rhs) => lhs.count() + rhs.count(),
+            NodeKind::Comparison(first, rest) => {
+                first.count() + rest.iter().map(|(_, node)| node.count()).sum::<usize>()
+            }
+            NodeKind::Match(scrutinee, arms) => {
+                scrutinee.count()
+                    + arms
+                        .iter()
+                        .map(|(pattern, body)| {
+                            let pattern = match &pattern.kind {
+                                PatternKind::Literal(node) => node.count(),
+                                _ => 1,
+                            };
+                            pattern + body.count()
+                        })
+                        .sum::<usize>()
+            }
+            NodeKind::Index(target, index) => target.count() + index.count(),
+            NodeKind::MemberAccess(target, _) => target.count(),
+            NodeKind::Slice(target, start, end) => {
+                target.count()
+                    + start.as_ref().map_or(0, |node| node.count())
+                    + end.as_ref().map_or(0, |node| node.count())
+            }
+            NodeKind::ListLiteral(items) => items.iter().map(Node::count).sum(),
+            NodeKind::MapLiteral(entries) => entries
+                .iter()
+                .map(|(key, value)| key.count() + value.count())
+                .sum(),
+            NodeKind::Import(_, _)
+            | NodeKind::Identifier(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::FloatLiteral(_)
+            | NodeKind::IntegerLiteral(_)
+            | NodeKind::BooleanLiteral(_)
+            | NodeKind::NoneLiteral => 0,
+        }
+    }
+}
+
 impl Display for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -206,28 +435,113 @@ impl<'a> Display for NodeFormatter<'a> {
         let node = self.node;
         write!(f, "{}", node.kind.variant_name())?;
         match &node.kind {
-            NodeKind::Return(expr) => {
+            NodeKind::Return(Some(expr)) => {
                 write!(f, "(\n{}\n)", self.child(expr))?;
             }
-            NodeKind::VarDeclaration(ident, expr) => {
+            NodeKind::Return(None) => write!(f, "()")?,
+            NodeKind::Loop(body) | NodeKind::Do(body) => {
+                write!(f, " {{\n{}\n}}", self.child(body))?
+            }
+            NodeKind::Break(Some(value)) => write!(f, "(\n{}\n)", self.child(value))?,
+            NodeKind::Break(None) => write!(f, "()")?,
+            NodeKind::VarDeclaration(ident, Some(expr)) => {
                 write!(f, "({}){{\n{}\n}}", ident, self.child(expr))?;
             }
+            NodeKind::VarDeclaration(ident, None) => write!(f, "({})", ident)?,
+            NodeKind::VarDestructure(targets, rest, value) => {
+                writeln!(f, "({} targets) {{", targets.len())?;
+                for target in targets {
+                    writeln!(f, "  {target}")?;
+                }
+                if let Some(rest) = rest {
+                    writeln!(f, "  ..{rest}")?;
+                }
+                write!(f, "{}\n}}", self.child(value))?;
+            }
+            NodeKind::Import(ident, path) => write!(f, "({} {:?})", ident, path)?,
+            NodeKind::If(branches, otherwise) => {
+                writeln!(f, "({} branches) {{", branches.len())?;
+                for (cond, then) in branches {
+                    writeln!(f, "{}\n{}", self.child(cond), self.child(then))?;
+                }
+                if let Some(otherwise) = otherwise {
+                    writeln!(f, "  else\n{}", self.child(otherwise))?;
+                }
+                write!(f, "}}")?;
+            }
+            NodeKind::Conditional(cond, then, otherwise) => {
+                write!(
+                    f,
+                    "(\n{}\n) {{\n{}\n}} else {{\n{}\n}}",
+                    self.child(cond),
+                    self.child(then),
+                    self.child(otherwise)
+                )?;
+            }
+            NodeKind::While(cond, body) => {
+                write!(f, "(\n{}\n) {{\n{}\n}}", self.child(cond), self.child(body))?;
+            }
+            NodeKind::For(ident, range, body) => {
+                write!(
+                    f,
+                    "({}) {{\n{}\n}} {{\n{}\n}}",
+                    ident,
+                    self.child(range),
+                    self.child(body)
+                )?;
+            }
+            NodeKind::Range(start, end, inclusive) => {
+                let symbol = if *inclusive { "..=" } else { ".." };
+                write!(
+                    f,
+                    "(`{symbol}`) {{\n{}\n{}\n}}",
+                    self.child(start),
+                    self.child(end)
+                )?;
+            }
+            NodeKind::Assignment(op, target, value) => {
+                if let Some(op) = op {
+                    write!(f, "({} `{}=`)", op.variant_name(), op.symbol())?;
+                }
+                write!(f, " {{\n{}\n{}\n}}", self.child(target), self.child(value))?;
+            }
             NodeKind::UnaryOperation(op, expr) => {
-                write!(f, "({}) {{\n{}\n}}", op.variant_name(), self.child(expr))?;
+                write!(
+                    f,
+                    "({} `{}`) {{\n{}\n}}",
+                    op.variant_name(),
+                    op.symbol(),
+                    self.child(expr)
+                )?;
             }
             NodeKind::BinaryOperation(op, lhs, rhs) => {
                 write!(
                     f,
-                    "({}) {{\n{}\n{}\n}}",
+                    "({} `{}`) {{\n{}\n{}\n}}",
                     op.variant_name(),
+                    op.symbol(),
                     self.child(lhs),
                     self.child(rhs)
                 )?;
             }
+            NodeKind::Comparison(first, rest) => {
+                writeln!(f, " {{\n{}", self.child(first))?;
+                for (op, node) in rest {
+                    writeln!(
+                        f,
+                        "  ({} `{}`)\n{}",
+                        op.variant_name(),
+                        op.symbol(),
+                        self.child(node)
+                    )?;
+                }
+                write!(f, "}}")?;
+            }
             NodeKind::StringLiteral(val) => write!(f, "({val:?})")?,
             NodeKind::FloatLiteral(val) => write!(f, "({val})")?,
             NodeKind::IntegerLiteral(val) => write!(f, "({val})")?,
             NodeKind::BooleanLiteral(val) => write!(f, "({val})")?,
+            NodeKind::NoneLiteral => (),
             NodeKind::Block(stmts) => {
                 writeln!(f, "({} statements) {{", stmts.len())?;
                 for stmt in stmts {
@@ -235,9 +549,173 @@ impl<'a> Display for NodeFormatter<'a> {
                 }
                 write!(f, "}}")?;
             }
+            NodeKind::Match(scrutinee, arms) => {
+                writeln!(f, "({} arms) {{\n{}", arms.len(), self.child(scrutinee))?;
+                for (pattern, body) in arms {
+                    writeln!(f, "  {pattern} =>\n{}", self.child(body))?;
+                }
+                write!(f, "}}")?;
+            }
+            NodeKind::Index(target, index) => {
+                write!(f, " {{\n{}\n{}\n}}", self.child(target), self.child(index))?;
+            }
+            NodeKind::MemberAccess(target, name) => {
+                write!(f, "({name}) {{\n{}\n}}", self.child(target))?;
+            }
+            NodeKind::Slice(target, start, end) => {
+                writeln!(f, " {{\n{}", self.child(target))?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => writeln!(f, "{}", self.child(bound))?,
+                        None => writeln!(f, "  _")?,
+                    }
+                }
+                write!(f, "}}")?;
+            }
+            NodeKind::ListLiteral(items) => {
+                writeln!(f, "({} items) {{", items.len())?;
+                for item in items {
+                    writeln!(f, "{}", self.child(item))?;
+                }
+                write!(f, "}}")?;
+            }
+            NodeKind::MapLiteral(entries) => {
+                writeln!(f, "({} entries) {{", entries.len())?;
+                for (key, value) in entries {
+                    writeln!(f, "{}\n{}", self.child(key), self.child(value))?;
+                }
+                write!(f, "}}")?;
+            }
             NodeKind::Identifier(val) => write!(f, "({val:?})")?,
         }
-        write!(f, "[{:?}]", self.node.span)?;
+        write!(f, "[{:#}]", self.node.span)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::lexer::Lexer;
+    use crate::files::push_source;
+    use crate::pipeline::testing::filename;
+
+    const OPERATORS: [Operator; 23] = [
+        Operator::Or,
+        Operator::And,
+        Operator::Not,
+        Operator::Plus,
+        Operator::Minus,
+        Operator::Star,
+        Operator::Slash,
+        Operator::FloorDiv,
+        Operator::GreaterThan,
+        Operator::LessThan,
+        Operator::GreaterThanEquals,
+        Operator::LessThanEquals,
+        Operator::Equals,
+        Operator::BangEquals,
+        Operator::BitAnd,
+        Operator::BitOr,
+        Operator::BitXor,
+        Operator::BitNot,
+        Operator::Shl,
+        Operator::Shr,
+        Operator::In,
+        Operator::NotIn,
+        Operator::Coalesce,
+    ];
+
+    /// Fails to compile when a variant is added, as a reminder to list it
+    /// in `OPERATORS`.
+    #[allow(unused)]
+    fn listed(op: Operator) {
+        match op {
+            Operator::Or
+            | Operator::And
+            | Operator::Not
+            | Operator::Plus
+            | Operator::Minus
+            | Operator::Star
+            | Operator::Slash
+            | Operator::FloorDiv
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::GreaterThanEquals
+            | Operator::LessThanEquals
+            | Operator::Equals
+            | Operator::BangEquals
+            | Operator::BitAnd
+            | Operator::BitOr
+            | Operator::BitXor
+            | Operator::BitNot
+            | Operator::Shl
+            | Operator::Shr
+            | Operator::In
+            | Operator::NotIn
+            | Operator::Coalesce => (),
+        }
+    }
+
+    #[test]
+    fn every_operator_has_a_symbol() {
+        for (i, op) in OPERATORS.iter().enumerate() {
+            let symbol = op.symbol();
+            assert!(
+                OPERATORS[..i].iter().all(|other| other.symbol() != symbol),
+                "{symbol} is used twice"
+            );
+        }
+    }
+
+    /// The kinds of the tokens `source` lexes as, without the EOF.
+    fn token_kinds(source: &str) -> Vec<TokenKind> {
+        let filename = filename();
+        push_source(filename, source.to_string());
+        let Ok(lexer) = Lexer::new(filename) else {
+            panic!("Failed to read {filename}");
+        };
+        lexer
+            .into_iter()
+            .map(|token| match token {
+                Ok(token) => token.kind,
+                Err(_) => panic!("Failed to lex {source:?}"),
+            })
+            .filter(|kind| *kind != TokenKind::EOF)
+            .collect()
+    }
+
+    /// The operators a token can stand for, as a prefix or as an infix.
+    fn operators(kind: TokenKind) -> Vec<Operator> {
+        let prefix = kind.as_prefix().map(|(op, _, _)| op);
+        let infix = kind.as_infix().map(|(op, _, _)| op);
+        prefix.into_iter().chain(infix).collect()
+    }
+
+    #[test]
+    fn the_lexer_and_symbols_agree() {
+        // Every symbol lexes as a token for its operator.
+        for op in OPERATORS {
+            let symbol = op.symbol();
+            let found = match token_kinds(symbol)[..] {
+                [kind] => operators(kind),
+                // The parser combines these two.
+                [TokenKind::Not, TokenKind::In] => vec![Operator::NotIn],
+                ref kinds => panic!("{symbol} lexes as {kinds:?}"),
+            };
+            assert!(found.contains(&op), "{symbol}");
+        }
+        // Every operator token is spelled as the symbol of its operator,
+        // except for `not`, which is another way to write `!`.
+        let tokens = "or ?? and == != > >= < <= in | ^ & << >> + - * / // ! not ~";
+        for kind in token_kinds(tokens) {
+            let found = operators(kind);
+            assert!(!found.is_empty(), "{kind} isn't an operator");
+            for op in found {
+                if kind != TokenKind::Not {
+                    assert_eq!(token_kinds(op.symbol()), [kind]);
+                }
+            }
+        }
+    }
+}
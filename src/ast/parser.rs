@@ -1,18 +1,26 @@
 use crate::ast::lexer::{Base, Lexer, LexerIterator};
 use crate::ast::span::Span;
+use crate::ast::symbol::Symbol;
 use crate::ast::token::{Token, TokenKind};
-use crate::ast::{Node, NodeKind};
-use crate::report::{Maybe, Report, ReportKind, ReportLevel, ReportSender, SpanToLabel};
+use crate::ast::{Node, NodeKind, Operator, Pattern, PatternKind};
+use crate::cancel::{CancelToken, Cancelled};
+use crate::report::{
+    Maybe, Report, ReportBuilder, ReportKind, ReportLevel, ReportSink, SpanToLabel,
+};
 use ariadne::Color;
 use name_variant::NamedVariant;
 use std::fmt::{Display, Formatter};
+use std::path::Path;
 use ParserError::*;
+use ParserWarning::*;
 
 #[derive(NamedVariant)]
 enum ParserError {
     SyntaxError(String),
     UnexpectedEOF,
     UnexpectedToken(TokenKind),
+    /// The dotted name of a module with no file to load it from.
+    ModuleNotFound(String),
 }
 
 impl Display for ParserError {
@@ -21,6 +29,7 @@ impl Display for ParserError {
         match self {
             UnexpectedToken(kind) => write!(f, " {kind}")?,
             SyntaxError(msg) => write!(f, " {msg}")?,
+            ModuleNotFound(name) => write!(f, " `{name}`")?,
             _ => (),
         }
         Ok(())
@@ -37,14 +46,35 @@ impl ReportKind for ParserError {
     }
 }
 
+#[derive(NamedVariant)]
+enum ParserWarning {
+    RawControlCharacter(char),
+}
+
+impl ReportKind for ParserWarning {
+    fn title(&self) -> String {
+        match self {
+            RawControlCharacter(c) => format!("{} {:?} in string literal", self.variant_name(), c),
+        }
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Warn
+    }
+}
+
 pub struct Parser<'contents> {
     lexer: std::iter::Peekable<LexerIterator<'contents>>,
     current: Token<'contents>,
-    reporter: ReportSender,
+    reporter: Box<dyn ReportSink>,
+    /// Every delimiter that has been opened but not yet closed, innermost
+    /// last, so reaching the end of the file can point at the culprit.
+    delimiters: Vec<(TokenKind, Span)>,
+    cancel: CancelToken,
 }
 
 impl<'contents> Parser<'contents> {
-    pub fn new(filename: &'static str, reporter: ReportSender) -> Maybe<Self> {
+    pub fn new<R: ReportSink + 'static>(filename: &'static str, reporter: R) -> Maybe<Self> {
         let mut lexer = Lexer::new(filename)?.into_iter().peekable();
         let current = loop {
             match lexer.next() {
@@ -56,10 +86,17 @@ impl<'contents> Parser<'contents> {
         Ok(Self {
             current,
             lexer,
-            reporter,
+            reporter: Box::new(reporter),
+            delimiters: Vec::new(),
+            cancel: CancelToken::new(),
         })
     }
 
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     fn report(&self, report: Box<Report>) {
         self.reporter.report(report);
     }
@@ -73,24 +110,42 @@ impl<'contents> Parser<'contents> {
         }
     }
 
-    fn skip_until<F: Fn(Token) -> bool>(&mut self, predicate: F) -> Option<Token> {
+    /// Skips to the start of the next statement after an error. Line
+    /// breaks inside delimiters left open by the error don't end the
+    /// statement, so recovery resumes after the broken expression rather
+    /// than partway through it. `depth` is how many delimiters were open
+    /// when the statement began, i.e. those of the enclosing blocks.
+    fn sync<F: Fn(Token) -> bool>(&mut self, depth: usize, predicate: F) {
         loop {
-            match self.current {
-                token if predicate(token) => break Some(self.current.clone()),
-                Token {
-                    kind: TokenKind::EOF,
-                    ..
-                } => break None,
-                _ => self.advance(),
+            let token = self.current;
+            if token.kind == TokenKind::EOF {
+                break;
             }
+            // Is it a new statement?
+            if self.delimiters.len() <= depth
+                && (matches!(
+                    token.kind,
+                    // This is where we will check for known statement beginners
+                    TokenKind::Semicolon
+                ) || token.newline_before
+                    || predicate(token))
+            {
+                break;
+            }
+            match token.kind {
+                TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => {
+                    self.delimiters.push((token.kind, token.span))
+                }
+                TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket
+                    if self.delimiters.len() > depth =>
+                {
+                    self.delimiters.pop();
+                }
+                _ => (),
+            }
+            self.advance();
         }
-    }
-
-    fn sync<F: Fn(Token) -> bool>(&mut self, predicate: F) {
-        self.skip_until(|token| /* Is it a new statement? */ matches!(token.kind,
-                // This is where we will check for known statement beginners
-                TokenKind::Semicolon
-            ) || token.newline_before || predicate(token));
+        self.delimiters.truncate(depth);
         if self.current.kind == TokenKind::Semicolon {
             self.advance();
         }
@@ -112,35 +167,27 @@ impl<'contents> Parser<'contents> {
                 if token.kind != TokenKind::EOF {
                     self.advance();
                 }
-                Ok(token.clone())
+                Ok(token)
             }
-            token if token.kind == TokenKind::EOF => Err(UnexpectedEOF
-                .make_labeled(token.span.labeled(message))
-                .into()),
+            token if token.kind == TokenKind::EOF => Err(self.unexpected_eof(token.span, message)),
             token => Err(UnexpectedToken(token.kind)
                 .make_labeled(token.span.labeled(message))
                 .into()),
         }
     }
 
-    fn consume_line(&mut self) -> Maybe<()> {
-        match self.current {
-            Token {
-                kind: TokenKind::Semicolon,
-                ..
-            } => self.advance(),
-            Token {
-                kind: TokenKind::EOF,
-                ..
-            } => (),
-            token if token.newline_before => (),
-            token => {
-                return Err(UnexpectedToken(token.kind)
-                    .make_labeled(token.span.labeled("Expected end of statement"))
-                    .into())
-            }
+    /// Reports reaching the end of the file at `span`, also labeling the
+    /// innermost delimiter that is still open, if any.
+    fn unexpected_eof<T: Display>(&self, span: Span, message: T) -> Box<ReportBuilder> {
+        let mut report = UnexpectedEOF.make_labeled(span.labeled(message));
+        if let Some((kind, opened)) = self.delimiters.last() {
+            report.push_label(opened.labeled(format!("Unclosed {kind} opened here")));
+            report.set_note(match self.delimiters.len() {
+                1 => "1 delimiter is still unclosed".to_string(),
+                n => format!("{n} delimiters are still unclosed"),
+            });
         }
-        Ok(())
+        report.into()
     }
 
     fn consume_line_or(&mut self, expect: TokenKind) -> Maybe<()> {
@@ -167,10 +214,47 @@ impl<'contents> Parser<'contents> {
         Ok(())
     }
 
+    fn at_line_end(&self) -> bool {
+        matches!(self.current.kind, TokenKind::Semicolon | TokenKind::EOF)
+            || self.current.newline_before
+    }
+
+    /// Whether a statement that can end without a value, like a bare
+    /// `return`, ends here: at the end of the line or of the block.
+    fn at_statement_end(&self) -> bool {
+        self.at_line_end() || self.current.kind == TokenKind::RightBrace
+    }
+
     fn consume_one(&mut self, expect: TokenKind) -> Maybe<Token<'contents>> {
         self.consume(|token| token.kind == expect, format!("Expected {expect}"))
     }
 
+    /// Warns about control characters written directly into a string
+    /// literal, since they are invisible in most editors.
+    fn lint_control_characters(&self, text: &str, span: Span) {
+        for (i, c) in text.char_indices() {
+            if !c.is_control() || matches!(c, '\n' | '\t') {
+                continue;
+            }
+            let escape = match c {
+                '\r' => "\\r".to_string(),
+                '\u{0008}' => "\\b".to_string(),
+                '\u{000C}' => "\\f".to_string(),
+                '\0' => "\\0".to_string(),
+                '\u{001B}' => "\\e".to_string(),
+                c if c.is_ascii() => format!("\\x{:02X}", c as u32),
+                c => format!("\\u{:04X}", c as u32),
+            };
+            self.report(
+                RawControlCharacter(c)
+                    .make_labeled(Span::at(span.filename, span.start + 1 + i).label())
+                    .with_help(format!("Write it as {escape} instead"))
+                    .finish()
+                    .into(),
+            );
+        }
+    }
+
     pub fn parse(&mut self) -> Box<Node> {
         self.parse_program()
     }
@@ -184,9 +268,19 @@ impl<'contents> Parser<'contents> {
 
     fn parse_block(&mut self, start: Span, closer: TokenKind) -> Maybe<Box<Node>> {
         let mut stmts = Vec::new();
-        let sync = |s: &mut Parser| s.sync(|token| token.kind == closer);
+        let depth = self.delimiters.len();
+        let sync = |s: &mut Parser| s.sync(depth, |token| token.kind == closer);
 
         while self.current.kind != closer && self.current.kind != TokenKind::EOF {
+            if self.cancel.is_cancelled() {
+                self.report(
+                    Cancelled
+                        .make_labeled(self.current.span.labeled("Stopped before this statement"))
+                        .finish()
+                        .into(),
+                );
+                return Ok(NodeKind::Block(stmts).make(start).into());
+            }
             match self.parse_statement() {
                 Ok(stmt) => match self.consume_line_or(closer) {
                     Ok(_) => stmts.push(*stmt),
@@ -206,27 +300,194 @@ impl<'contents> Parser<'contents> {
         Ok(NodeKind::Block(stmts).make(start.extend(end)).into())
     }
 
+    /// Parses a block wrapped in braces.
+    fn parse_braced_block(&mut self) -> Maybe<Box<Node>> {
+        let start = self.consume_one(TokenKind::LeftBrace)?.span;
+        // On error the brace stays open, for sync to close.
+        self.delimiters.push((TokenKind::LeftBrace, start));
+        let block = self.parse_block(start, TokenKind::RightBrace)?;
+        self.delimiters.pop();
+        Ok(block)
+    }
+
+    /// Parses an `if`, with the current token being `if`.
+    fn parse_if(&mut self) -> Maybe<Box<Node>> {
+        let start = self.current.span;
+        self.advance();
+        let cond = self.parse_expression(0)?;
+        if self.current.kind == TokenKind::Then {
+            return self.parse_conditional(start, cond);
+        }
+        let then = self.parse_braced_block()?;
+        let mut end = then.span;
+        let mut branches = vec![(*cond, *then)];
+        let mut otherwise = None;
+        loop {
+            let kind = self.current.kind;
+            match kind {
+                TokenKind::Elif => (),
+                TokenKind::Else if self.peek_is(TokenKind::If) => self.advance(),
+                TokenKind::Else => {
+                    self.advance();
+                    let block = self.parse_braced_block()?;
+                    end = block.span;
+                    otherwise = Some(block);
+                    break;
+                }
+                _ => break,
+            }
+            // Past the `elif`, or the `if` of `else if`.
+            self.advance();
+            let cond = self.parse_expression(0)?;
+            let then = self.parse_braced_block()?;
+            end = then.span;
+            branches.push((*cond, *then));
+        }
+        Ok(NodeKind::If(branches, otherwise)
+            .make(start.extend(end))
+            .into())
+    }
+
+    /// Parses the rest of `if cond then a else b` from `then`, where `start`
+    /// is the span of `if`.
+    fn parse_conditional(&mut self, start: Span, cond: Box<Node>) -> Maybe<Box<Node>> {
+        self.consume_one(TokenKind::Then)?;
+        let then = self.parse_expression(0)?;
+        if self.current.kind != TokenKind::Else {
+            return Err(
+                SyntaxError("Conditional expression without else".to_string())
+                    .make_labeled(
+                        start
+                            .extend(then.span)
+                            .labeled("This needs a value for when the condition is false"),
+                    )
+                    .with_help("Add `else` and a value after it")
+                    .into(),
+            );
+        }
+        self.advance();
+        let otherwise = self.parse_expression(0)?;
+        let span = start.extend(otherwise.span);
+        Ok(NodeKind::Conditional(cond, then, otherwise)
+            .make(span)
+            .into())
+    }
+
+    /// Parses a `while` loop, with the current token being `while`.
+    fn parse_while(&mut self) -> Maybe<Box<Node>> {
+        let start = self.current.span;
+        self.advance();
+        let cond = self.parse_expression(0)?;
+        let body = self.parse_braced_block()?;
+        let span = start.extend(body.span);
+        Ok(NodeKind::While(cond, body).make(span).into())
+    }
+
+    /// Parses a `for` loop over a range, with the current token being
+    /// `for`.
+    fn parse_for(&mut self) -> Maybe<Box<Node>> {
+        let start = self.current.span;
+        self.advance();
+        let ident = self.consume_one(TokenKind::Identifier)?.text;
+        self.consume_one(TokenKind::In)?;
+        let range = self.parse_expression(0)?;
+        let body = self.parse_braced_block()?;
+        let span = start.extend(body.span);
+        Ok(NodeKind::For(Symbol::intern(ident), range, body)
+            .make(span)
+            .into())
+    }
+
+    /// Parses `import a.b`, or `import a.b as c`, which loads the file
+    /// `a/b.moon` next to the importing file. The module is bound to `c`,
+    /// or else to the last part of its name.
+    fn parse_import(&mut self) -> Maybe<Box<Node>> {
+        let start = self.current.span;
+        self.advance();
+        let first = self.consume_one(TokenKind::Identifier)?;
+        let mut parts = vec![first.text];
+        let mut span = start.extend(first.span);
+        while self.current.kind == TokenKind::Dot {
+            self.advance();
+            let part = self.consume_one(TokenKind::Identifier)?;
+            parts.push(part.text);
+            span = span.extend(part.span);
+        }
+        let mut name = *parts.last().unwrap();
+        if self.current.kind == TokenKind::As {
+            self.advance();
+            let alias = self.consume_one(TokenKind::Identifier)?;
+            name = alias.text;
+            span = span.extend(alias.span);
+        }
+        let mut path = Path::new(start.filename)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(parts.join("/"));
+        path.set_extension("moon");
+        if !path.is_file() {
+            return Err(ModuleNotFound(parts.join("."))
+                .make_labeled(span.labeled("Imported here"))
+                .with_note(format!("Looked for {}", path.display()))
+                .into());
+        }
+        let path = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+        Ok(NodeKind::Import(Symbol::intern(name), path)
+            .make(span)
+            .into())
+    }
+
     fn parse_statement(&mut self) -> Maybe<Box<Node>> {
         let Token { kind, span, .. } = self.current;
         match kind {
+            TokenKind::If => self.parse_if(),
+            TokenKind::While => self.parse_while(),
+            TokenKind::For => self.parse_for(),
+            TokenKind::Import => self.parse_import(),
+            TokenKind::Break => {
+                self.advance();
+                if self.at_statement_end() {
+                    return Ok(NodeKind::Break(None).make(span).into());
+                }
+                let value = self.parse_expression(0)?;
+                let span = span.extend(value.span);
+                Ok(NodeKind::Break(Some(value)).make(span).into())
+            }
+            TokenKind::LeftBrace => self.parse_braced_block(),
             TokenKind::Return => {
                 self.advance();
+                if self.at_statement_end() {
+                    return Ok(NodeKind::Return(None).make(span).into());
+                }
                 let expr = self.parse_expression(0)?;
-                Ok(NodeKind::Return(expr).make(span).into())
+                let span = span.extend(expr.span);
+                Ok(NodeKind::Return(Some(expr)).make(span).into())
             }
             TokenKind::Let => {
                 self.advance();
-                let ident = self.consume_one(TokenKind::Identifier)?.text;
+                if self.current.kind == TokenKind::LeftBracket || self.peek_is(TokenKind::Comma) {
+                    return self.parse_destructure(span);
+                }
+                let Token {
+                    text: ident,
+                    span: ident_span,
+                    ..
+                } = self.consume_one(TokenKind::Identifier)?;
                 // let type_annotation: Option<Type> = match self.current.kind {
                 //     TokenKind::Colon => {
                 //         unimplemented!("Have not implemented type checking yet...")
                 //     }
                 //     _ => None
                 // };
+                if self.at_statement_end() {
+                    return Ok(NodeKind::VarDeclaration(Symbol::intern(ident), None)
+                        .make(span.extend(ident_span))
+                        .into());
+                }
                 self.consume_one(TokenKind::Equals)?;
                 let expr = self.parse_expression(0)?;
                 let span = span.extend(expr.span);
-                Ok(NodeKind::VarDeclaration(ident.to_string(), expr)
+                Ok(NodeKind::VarDeclaration(Symbol::intern(ident), Some(expr))
                     .make(span)
                     .into())
             }
@@ -234,6 +495,97 @@ impl<'contents> Parser<'contents> {
         }
     }
 
+    /// Parses the rest of a destructuring `let` after the keyword, from the
+    /// names, which may be in brackets, through to the value.
+    fn parse_destructure(&mut self, span: Span) -> Maybe<Box<Node>> {
+        let bracketed = self.current.kind == TokenKind::LeftBracket;
+        if bracketed {
+            self.delimiters
+                .push((TokenKind::LeftBracket, self.current.span));
+            self.advance();
+        }
+        let mut targets = Vec::new();
+        let mut rest = None;
+        while !(bracketed && self.current.kind == TokenKind::RightBracket) {
+            if self.current.kind == TokenKind::DotDot {
+                let dots = self.current.span;
+                self.advance();
+                let target = self.parse_let_target()?;
+                if self.current.kind == TokenKind::Comma
+                    && !(bracketed && self.peek_is(TokenKind::RightBracket))
+                {
+                    return Err(SyntaxError("Nothing can follow a catch-all".to_string())
+                        .make_labeled(self.current.span.labeled("Expected the end of the names"))
+                        .with_label(
+                            dots.extend(target.span)
+                                .labeled("The catch-all takes every element left")
+                                .with_color(Color::Blue),
+                        )
+                        .into());
+                }
+                rest = Some(target);
+            } else {
+                targets.push(self.parse_let_target()?);
+            }
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+        }
+        if bracketed {
+            self.consume_one(TokenKind::RightBracket)?;
+            self.delimiters.pop();
+        }
+        if targets.len() > u8::MAX as usize {
+            return Err(SyntaxError("Too many names to destructure".to_string())
+                .make_labeled(span.labeled("In this `let`"))
+                .with_note(format!("A `let` can bind at most {} elements", u8::MAX))
+                .into());
+        }
+        let mut bound: Vec<&Pattern> = Vec::new();
+        for target in targets.iter().chain(&rest) {
+            let PatternKind::Binding(name) = target.kind else {
+                continue;
+            };
+            if let Some(first) = bound
+                .iter()
+                .find(|other| matches!(other.kind, PatternKind::Binding(other) if other == name))
+            {
+                return Err(SyntaxError(format!("`{name}` is bound twice"))
+                    .make_labeled(target.span.labeled("Bound again here"))
+                    .with_label(
+                        first
+                            .span
+                            .labeled("First bound here")
+                            .with_color(Color::Blue),
+                    )
+                    .into());
+            }
+            bound.push(target);
+        }
+        self.consume_one(TokenKind::Equals)?;
+        let value = self.parse_expression(0)?;
+        let span = span.extend(value.span);
+        Ok(NodeKind::VarDestructure(targets, rest, value)
+            .make(span)
+            .into())
+    }
+
+    /// A name for a destructuring `let` to bind, or `_` to skip an element.
+    fn parse_let_target(&mut self) -> Maybe<Pattern> {
+        let pattern = self.parse_pattern()?;
+        if !pattern.is_irrefutable() {
+            return Err(SyntaxError("A `let` can't match a literal".to_string())
+                .make_labeled(pattern.span.labeled("Expected a name or `_`"))
+                .with_help("Use `match` to test a value against literals")
+                .into());
+        }
+        Ok(pattern)
+    }
+
+    /// The left binding power of `..` and `..=`.
+    const RANGE_BP: u8 = 5;
+
     fn parse_expression(&mut self, min_bp: u8) -> Maybe<Box<Node>> {
         let mut lhs = match self.current.kind.as_prefix() {
             Some((op, _, rbp)) => {
@@ -245,7 +597,53 @@ impl<'contents> Parser<'contents> {
             }
             _ => self.parse_atom()?,
         };
+        // Whether `lhs` is a comparison made by this loop, which a further
+        // comparison chains onto. A parenthesized one isn't.
+        let mut chaining = false;
         loop {
+            // Member access and indexing bind tighter than any operator.
+            if self.current.kind == TokenKind::Dot {
+                self.advance();
+                let name = self.consume_one(TokenKind::Identifier)?;
+                let span = lhs.span.extend(name.span);
+                lhs = NodeKind::MemberAccess(lhs, Symbol::intern(name.text))
+                    .make(span)
+                    .into();
+                chaining = false;
+                continue;
+            }
+            // A bracket starting a new line begins a list instead.
+            if self.current.kind == TokenKind::LeftBracket && !self.current.newline_before {
+                let open = self.current.span;
+                self.advance();
+                self.delimiters.push((TokenKind::LeftBracket, open));
+                let index = match self.current.kind {
+                    TokenKind::Colon => None,
+                    _ => Some(self.parse_expression(0)?),
+                };
+                // A colon makes it a slice, where either bound can be left out.
+                let slice_end = if self.current.kind == TokenKind::Colon {
+                    self.advance();
+                    Some(match self.current.kind {
+                        TokenKind::RightBracket => None,
+                        _ => Some(self.parse_expression(0)?),
+                    })
+                } else {
+                    None
+                };
+                let end = self.consume_one(TokenKind::RightBracket)?.span;
+                self.delimiters.pop();
+                let span = lhs.span.extend(end);
+                lhs = match (index, slice_end) {
+                    (index, Some(slice_end)) => NodeKind::Slice(lhs, index, slice_end),
+                    (Some(index), None) => NodeKind::Index(lhs, index),
+                    (None, None) => unreachable!("A missing index is only allowed in a slice"),
+                }
+                .make(span)
+                .into();
+                chaining = false;
+                continue;
+            }
             if let Some((op, lbp, ())) = self.current.kind.as_postfix() {
                 if lbp < min_bp {
                     break;
@@ -253,40 +651,263 @@ impl<'contents> Parser<'contents> {
                 let span = self.current.span;
                 self.advance();
                 lhs = NodeKind::UnaryOperation(op, lhs).make(span).into();
+                chaining = false;
+                continue;
+            }
+            if let kind @ (TokenKind::DotDot | TokenKind::DotDotEquals) = self.current.kind {
+                // Ranges bind looser than arithmetic and bitwise operators,
+                // but tighter than comparisons.
+                if Self::RANGE_BP < min_bp {
+                    break;
+                }
+                if let NodeKind::Range(..) = lhs.kind {
+                    return Err(SyntaxError("Ranges can't be chained".to_string())
+                        .make_labeled(self.current.span.labeled("A range doesn't continue here"))
+                        .with_label(lhs.span.labeled("After this range").with_color(Color::Blue))
+                        .into());
+                }
+                self.advance();
+                let end = self.parse_expression(Self::RANGE_BP + 1)?;
+                let span = lhs.span.extend(end.span);
+                lhs = NodeKind::Range(lhs, end, kind == TokenKind::DotDotEquals)
+                    .make(span)
+                    .into();
+                chaining = false;
                 continue;
             }
-            let Some((op, lbp, rbp)) = self.current.kind.as_infix() else {
+            let compound = self.current.kind.as_compound_assignment();
+            if self.current.kind == TokenKind::Equals || compound.is_some() {
+                // Assignment binds loosest of all, and to the right, so
+                // `a = b = 1` assigns 1 to both.
+                if min_bp > 0 {
+                    break;
+                }
+                if !matches!(lhs.kind, NodeKind::Identifier(_)) {
+                    return Err(SyntaxError("Invalid assignment target".to_string())
+                        .make_labeled(lhs.span.labeled("Only variables can be assigned to"))
+                        .into());
+                }
+                self.advance();
+                let value = self.parse_expression(0)?;
+                let span = lhs.span.extend(value.span);
+                lhs = NodeKind::Assignment(compound, lhs, value).make(span).into();
+                chaining = false;
+                continue;
+            }
+            // `not` followed by `in` is the one infix operator spelled with
+            // two tokens, so `x not in xs` means `!(x in xs)` rather than
+            // starting a new expression at `not`.
+            let negated_in = self.current.kind == TokenKind::Not && self.peek_is(TokenKind::In);
+            let infix = if negated_in {
+                TokenKind::In
+                    .as_infix()
+                    .map(|(_, lbp, rbp)| (Operator::NotIn, lbp, rbp))
+            } else {
+                self.current.kind.as_infix()
+            };
+            let Some((op, lbp, rbp)) = infix else {
                 break;
             };
             if lbp < min_bp {
                 break;
             }
+            if negated_in {
+                self.advance();
+            }
             self.advance();
             let rhs = self.parse_expression(rbp)?;
             let span = lhs.span.extend(rhs.span);
-            lhs = NodeKind::BinaryOperation(op, lhs, rhs).make(span).into();
+            lhs = if chaining && op.is_compound() {
+                let (first, mut rest) = match lhs.kind {
+                    NodeKind::BinaryOperation(l_op, first, middle) => {
+                        (first, vec![(l_op, *middle)])
+                    }
+                    NodeKind::Comparison(first, rest) => (first, rest),
+                    _ => unreachable!("Only comparisons are chained onto"),
+                };
+                rest.push((op, *rhs));
+                NodeKind::Comparison(first, rest).make(span).into()
+            } else {
+                NodeKind::BinaryOperation(op, lhs, rhs).make(span).into()
+            };
+            chaining = op.is_compound();
         }
         Ok(lhs)
     }
 
+    /// Parses expressions separated by commas, allowing a trailing comma,
+    /// up to but not including `closer`.
+    fn parse_comma_separated(&mut self, closer: TokenKind) -> Maybe<Vec<Node>> {
+        let mut items = Vec::new();
+        while self.current.kind != closer {
+            items.push(*self.parse_expression(0)?);
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+        }
+        Ok(items)
+    }
+
+    /// Parses the `key: value` entries of a map literal, up to but not
+    /// including the closing brace. A key written as a bare name is that
+    /// name as a string, like `{ x: 1 }`; anything else is an expression.
+    fn parse_map_entries(&mut self) -> Maybe<Vec<(Node, Node)>> {
+        let mut entries = Vec::new();
+        while self.current.kind != TokenKind::RightBrace {
+            let Token {
+                kind, text, span, ..
+            } = self.current;
+            let key = if kind == TokenKind::Identifier && self.peek_is(TokenKind::Colon) {
+                self.advance();
+                NodeKind::StringLiteral(text.to_string()).make(span)
+            } else {
+                *self.parse_expression(0)?
+            };
+            self.consume_one(TokenKind::Colon)?;
+            let value = *self.parse_expression(0)?;
+            entries.push((key, value));
+            if self.current.kind != TokenKind::Comma {
+                break;
+            }
+            self.advance();
+        }
+        Ok(entries)
+    }
+
+    /// Parses a `match` expression, with the current token being `match`.
+    /// Arms are separated by commas or line breaks.
+    fn parse_match(&mut self) -> Maybe<Box<Node>> {
+        let start = self.current.span;
+        self.advance();
+        let scrutinee = self.parse_expression(0)?;
+        let open = self.consume_one(TokenKind::LeftBrace)?.span;
+        self.delimiters.push((TokenKind::LeftBrace, open));
+        let mut arms = Vec::new();
+        while self.current.kind != TokenKind::RightBrace {
+            let pattern = self.parse_pattern()?;
+            self.consume_one(TokenKind::FatArrow)?;
+            let body = *self.parse_expression(0)?;
+            arms.push((pattern, body));
+            if self.current.kind == TokenKind::Comma {
+                self.advance();
+            } else if !self.current.newline_before {
+                break;
+            }
+        }
+        let end = self.consume_one(TokenKind::RightBrace)?.span;
+        self.delimiters.pop();
+        Ok(NodeKind::Match(scrutinee, arms)
+            .make(start.extend(end))
+            .into())
+    }
+
+    /// Parses a pattern of a `match` arm: a literal, a name to bind, or `_`.
+    fn parse_pattern(&mut self) -> Maybe<Pattern> {
+        let Token {
+            kind, text, span, ..
+        } = self.current;
+        let kind = match kind {
+            TokenKind::Identifier => {
+                self.advance();
+                match text {
+                    "_" => PatternKind::Wildcard,
+                    _ => PatternKind::Binding(Symbol::intern(text)),
+                }
+            }
+            TokenKind::Minus => {
+                self.advance();
+                let literal = self.parse_atom()?;
+                if !matches!(
+                    literal.kind,
+                    NodeKind::IntegerLiteral(_) | NodeKind::FloatLiteral(_)
+                ) {
+                    return Err(
+                        SyntaxError("Only numbers can be negated in a pattern".into())
+                            .make_labeled(literal.span.label())
+                            .into(),
+                    );
+                }
+                let span = span.extend(literal.span);
+                PatternKind::Literal(
+                    NodeKind::UnaryOperation(Operator::Minus, literal)
+                        .make(span)
+                        .into(),
+                )
+            }
+            TokenKind::IntegerLiteralBin
+            | TokenKind::IntegerLiteralDec
+            | TokenKind::IntegerLiteralHex
+            | TokenKind::IntegerLiteralOct
+            | TokenKind::FloatLiteral
+            | TokenKind::StringLiteral
+            | TokenKind::BooleanLiteral
+            | TokenKind::NoneLiteral => PatternKind::Literal(self.parse_atom()?),
+            _ => {
+                return Err(SyntaxError("Expected a pattern".into())
+                    .make_labeled(span.labeled("A literal, a name or `_` goes here"))
+                    .into())
+            }
+        };
+        let span = match &kind {
+            PatternKind::Literal(node) => node.span,
+            _ => span,
+        };
+        Ok(Pattern { kind, span })
+    }
+
     fn parse_atom(&mut self) -> Maybe<Box<Node>> {
         let Token {
             kind, text, span, ..
         } = self.current;
         match kind {
+            TokenKind::If => self.parse_if(),
+            TokenKind::Match => self.parse_match(),
+            TokenKind::Loop => {
+                self.advance();
+                let body = self.parse_braced_block()?;
+                let span = span.extend(body.span);
+                Ok(NodeKind::Loop(body).make(span).into())
+            }
+            TokenKind::Do => {
+                self.advance();
+                let body = self.parse_braced_block()?;
+                let span = span.extend(body.span);
+                Ok(NodeKind::Do(body).make(span).into())
+            }
+            TokenKind::LeftBracket => {
+                self.advance();
+                self.delimiters.push((kind, span));
+                let items = self.parse_comma_separated(TokenKind::RightBracket)?;
+                let end = self.consume_one(TokenKind::RightBracket)?.span;
+                self.delimiters.pop();
+                Ok(NodeKind::ListLiteral(items).make(span.extend(end)).into())
+            }
+            TokenKind::LeftBrace => {
+                self.advance();
+                self.delimiters.push((kind, span));
+                let entries = self.parse_map_entries()?;
+                let end = self.consume_one(TokenKind::RightBrace)?.span;
+                self.delimiters.pop();
+                Ok(NodeKind::MapLiteral(entries).make(span.extend(end)).into())
+            }
             TokenKind::LeftParen => {
                 self.advance();
+                // On error the delimiter stays open, for sync to close.
+                self.delimiters.push((kind, span));
                 let mut expr = self.parse_expression(0)?;
                 let end = self.consume_one(TokenKind::RightParen)?.span;
+                self.delimiters.pop();
                 expr.span = span.extend(end);
                 Ok(expr)
             }
             TokenKind::Identifier => {
                 self.advance();
-                Ok(NodeKind::Identifier(text.to_string()).make(span).into())
+                Ok(NodeKind::Identifier(Symbol::intern(text)).make(span).into())
             }
             TokenKind::StringLiteral => {
                 self.advance();
+                self.lint_control_characters(text, span);
                 Ok(
                     NodeKind::StringLiteral(StringParser::new(text, span).parse()?)
                         .make(span)
@@ -297,6 +918,10 @@ impl<'contents> Parser<'contents> {
                 self.advance();
                 Ok(NodeKind::BooleanLiteral(text.eq("True")).make(span).into())
             }
+            TokenKind::NoneLiteral => {
+                self.advance();
+                Ok(NodeKind::NoneLiteral.make(span).into())
+            }
             TokenKind::FloatLiteral => {
                 self.advance();
                 let val = text.parse().map_err(|err| {
@@ -319,7 +944,7 @@ impl<'contents> Parser<'contents> {
                     TokenKind::IntegerLiteralHex => (Base::Hexadecimal, 16),
                     _ => unreachable!(),
                 };
-                let val = usize::from_str_radix(text, radix).map_err(|err| {
+                let val = i64::from_str_radix(text, radix).map_err(|err| {
                     Box::new(
                         SyntaxError(format!("Invalid {base:?} Integer literal"))
                             .make_labeled(span.label())
@@ -328,9 +953,7 @@ impl<'contents> Parser<'contents> {
                 })?;
                 Ok(NodeKind::IntegerLiteral(val).make(span).into())
             }
-            TokenKind::EOF => Err(UnexpectedEOF
-                .make_labeled(span.labeled("Expected an expression"))
-                .into()),
+            TokenKind::EOF => Err(self.unexpected_eof(span, "Expected an expression")),
             _ => {
                 self.advance();
                 Err(UnexpectedToken(kind).make_labeled(span.label()).into())
@@ -379,6 +1002,29 @@ impl<'contents> StringParser<'contents> {
         Span::at(self.span.filename, self.span.start + start + 1)
     }
 
+    fn expect_hex_digits(&mut self, code_start: usize, count: usize) -> Maybe<()> {
+        for _ in 0..count {
+            match self.current_char {
+                Some('0'..='9' | 'a'..='f' | 'A'..='F') => self.advance(),
+                Some(c) => {
+                    return Err(
+                        SyntaxError(format!("Unexpected character {c:?} for escape code"))
+                            .make_labeled(self.span_at(self.current_index).labeled("here"))
+                            .with_label(self.span_from(code_start).label().with_color(Color::Blue))
+                            .into(),
+                    );
+                }
+                None => {
+                    return Err(SyntaxError("Unexpected end of string.".to_string())
+                        .make_labeled(self.span_at(self.current_index).labeled("here"))
+                        .with_label(self.span_from(code_start).label().with_color(Color::Blue))
+                        .into())
+                }
+            };
+        }
+        Ok(())
+    }
+
     pub fn parse(&mut self) -> Maybe<String> {
         let mut buf = String::with_capacity(self.source.len());
         while let Some(char) = self.current_char {
@@ -396,41 +1042,27 @@ impl<'contents> StringParser<'contents> {
                         'b' => buf.push('\u{0008}'),
                         'f' => buf.push('\u{000C}'),
                         '0' => buf.push('\0'),
-                        'u' => {
+                        'e' => buf.push('\u{001B}'),
+                        'x' => {
                             let code_start = self.current_index;
-                            for _ in 0..4 {
-                                match self.current_char {
-                                    Some('0'..='9' | 'a'..='f' | 'A'..='F') => self.advance(),
-                                    Some(c) => {
-                                        return Err(SyntaxError(format!(
-                                            "Unexpected character {c:?} for escape code"
-                                        ))
-                                        .make_labeled(
-                                            self.span_at(self.current_index).labeled("here"),
-                                        )
-                                        .with_label(
-                                            self.span_from(code_start)
-                                                .label()
-                                                .with_color(Color::Blue),
-                                        )
-                                        .into());
-                                    }
-                                    None => {
-                                        return Err(SyntaxError(
-                                            "Unexpected end of string.".to_string(),
-                                        )
-                                        .make_labeled(
-                                            self.span_at(self.current_index).labeled("here"),
-                                        )
-                                        .with_label(
-                                            self.span_from(code_start)
-                                                .label()
-                                                .with_color(Color::Blue),
-                                        )
-                                        .into())
-                                    }
-                                };
+                            self.expect_hex_digits(code_start, 2)?;
+                            let code_text = &self.source[code_start..self.current_index];
+                            let val = u8::from_str_radix(code_text, 16)
+                                .expect("Escape digits were checked to be hex");
+                            // Only ASCII, so that \xNN never means a byte of
+                            // UTF-8 in one place and a code point in another.
+                            if !val.is_ascii() {
+                                return Err(SyntaxError(format!("\\x{code_text} is above \\x7F"))
+                                    .make_labeled(self.span_from(code_start).label())
+                                    .with_label(self.span.label().with_color(Color::Blue))
+                                    .with_help(format!("Write U+00{code_text} as \\u00{code_text}"))
+                                    .into());
                             }
+                            buf.push(val as char);
+                        }
+                        'u' => {
+                            let code_start = self.current_index;
+                            self.expect_hex_digits(code_start, 4)?;
                             let code_span = self.span(code_start, self.current_index);
                             let code_text = &self.source[code_start..self.current_index];
                             let val = u16::from_str_radix(code_text, 16).map_err(|e| {
@@ -445,7 +1077,7 @@ impl<'contents> StringParser<'contents> {
                                     SyntaxError(format!(
                                         "Invalid Unicode Escape Sequence: {code_text}"
                                     ))
-                                    .make_labeled(code_span.label())
+                                    .make_labeled(code_span.labeled(e))
                                     .with_label(self.span.label().with_color(Color::Blue))
                                 })?;
                             buf.push(u_char);
@@ -469,3 +1101,281 @@ impl<'contents> StringParser<'contents> {
         Ok(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::NodeKind;
+    use crate::cancel::CancelToken;
+    use crate::pipeline::parse;
+    use crate::pipeline::testing::{compile, compile_err, eval, filename, titles, tree};
+    use crate::vm::Value;
+
+    /// The start of each label of the first report titled `title` in
+    /// `source`, with its message, and the report's note.
+    fn labels(source: &str, title: &str) -> (Vec<(usize, String)>, Option<String>) {
+        let diagnostics = compile_err(source);
+        let report = diagnostics
+            .reports
+            .iter()
+            .find(|report| report.title() == title)
+            .unwrap_or_else(|| panic!("No {title} report"));
+        let labels = report
+            .labels()
+            .into_iter()
+            .map(|(span, message)| (span.start, message.unwrap_or_default().to_string()))
+            .collect();
+        (labels, report.note().map(str::to_string))
+    }
+
+    /// Asserts that `source` parses into the same tree as `parenthesized`,
+    /// which spells out the grouping it should have.
+    fn parses_as(source: &str, parenthesized: &str) {
+        assert_eq!(tree(source), tree(parenthesized), "{source}");
+    }
+
+    #[test]
+    fn unterminated_string_labels_its_opener() {
+        let (labels, _) = labels("let x = \"abc", "UnterminatedString");
+        assert_eq!(
+            labels,
+            [
+                (12, "Expected a closing \"".to_string()),
+                (8, "String opened here".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn eof_labels_the_innermost_unclosed_delimiter() {
+        let (labels, note) = labels("let x = [1, (2 + {", "UnexpectedEOF");
+        assert_eq!(
+            labels[1],
+            (17, "Unclosed LeftBrace opened here".to_string())
+        );
+        assert_eq!(note.as_deref(), Some("3 delimiters are still unclosed"));
+    }
+
+    #[test]
+    fn eof_ignores_closed_delimiters() {
+        let (labels, note) = labels("let x = [[1, 2], (3", "UnexpectedEOF");
+        assert_eq!(
+            labels[1],
+            (17, "Unclosed LeftParen opened here".to_string())
+        );
+        assert_eq!(note.as_deref(), Some("2 delimiters are still unclosed"));
+    }
+
+    #[test]
+    fn eof_outside_delimiters_has_no_opener() {
+        let (labels, note) = labels("let x = 1 +", "UnexpectedEOF");
+        assert_eq!(labels.len(), 1);
+        assert_eq!(note, None);
+    }
+
+    #[test]
+    fn destructuring_a_name_twice() {
+        let (twice, _) = labels("let a, b, a = [1, 2, 3]", "SyntaxError `a` is bound twice");
+        assert_eq!(
+            twice,
+            [
+                (10, "Bound again here".to_string()),
+                (4, "First bound here".to_string()),
+            ]
+        );
+        let (rest, _) = labels("let [a, ..a] = [1]", "SyntaxError `a` is bound twice");
+        assert_eq!(rest[0].0, 10);
+    }
+
+    #[test]
+    fn destructuring_after_the_rest() {
+        let (labels, _) = labels(
+            "let a, ..r, b = [1]",
+            "SyntaxError Nothing can follow a catch-all",
+        );
+        assert_eq!(labels[0], (10, "Expected the end of the names".to_string()));
+        // A trailing comma is fine inside brackets.
+        assert_eq!(eval("let [a, ..r,] = [1, 2]\nreturn a"), Value::Integer(1));
+    }
+
+    #[test]
+    fn bare_return() {
+        // The expression on the next line is a statement of its own.
+        assert_eq!(
+            tree("return\nx"),
+            "Block(2 statements) {\n  Return()\n  Identifier(\"x\")\n}"
+        );
+        assert_eq!(
+            tree("return; x"),
+            "Block(2 statements) {\n  Return()\n  Identifier(\"x\")\n}"
+        );
+        assert_eq!(tree("return"), "Block(1 statements) {\n  Return()\n}");
+        assert_eq!(
+            tree("return x"),
+            "Block(1 statements) {\n  Return(\n    Identifier(\"x\")\n  )\n}"
+        );
+    }
+
+    #[test]
+    fn bare_return_runs_the_next_line() {
+        assert_eq!(eval("return\n5"), Value::None);
+    }
+
+    #[test]
+    fn bare_return_before_a_closing_brace() {
+        assert_eq!(
+            tree("if x { return }"),
+            "Block(1 statements) {\n  If(1 branches) {\n    Identifier(\"x\")\n    Block(1 statements) {\n      Return()\n    }\n  }\n}"
+        );
+        assert_eq!(eval("if True { return }\nreturn 1"), Value::None);
+    }
+
+    #[test]
+    fn let_without_a_value() {
+        for source in ["let x\nreturn x", "let x; return x", "{ let x }", "let x"] {
+            assert!(
+                tree(source).contains("VarDeclaration(x)"),
+                "{source:?} gave\n{}",
+                tree(source)
+            );
+            assert_eq!(eval(source), Value::None, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let cases = [
+            (r#""\e[1m""#, "\u{1B}[1m"),
+            (r#""\x41\x7f""#, "A\u{7F}"),
+            (r#""é""#, "é"),
+            (r#""\\\"\'\n\r\t\b\f\0""#, "\\\"'\n\r\t\u{8}\u{C}\0"),
+        ];
+        for (literal, expected) in cases {
+            assert_eq!(
+                eval(&format!("return {literal}")),
+                Value::String(expected.into()),
+                "{literal}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_hex_escapes() {
+        let (escape, _) = labels(r#"return "\x4""#, "SyntaxError Unexpected end of string.");
+        assert_eq!(escape[0], (11, "here".to_string()));
+        let (escape, _) = labels(
+            r#"return "\xZZ""#,
+            "SyntaxError Unexpected character 'Z' for escape code",
+        );
+        assert_eq!(escape[0], (10, "here".to_string()));
+        let (escape, _) = labels(r#"return "\xFF""#, r"SyntaxError \xFF is above \x7F");
+        assert_eq!(escape[0].0, 10);
+    }
+
+    #[test]
+    fn raw_control_characters_are_linted() {
+        let diagnostics = compile("return \"a\u{7}b\"");
+        let report = diagnostics
+            .reports
+            .iter()
+            .find(|report| report.title() == "RawControlCharacter '\\u{7}' in string literal")
+            .unwrap_or_else(|| panic!("No lint in {:?}", titles(&diagnostics)));
+        assert_eq!(report.labels()[0].0.start, 9);
+    }
+
+    #[test]
+    fn recovery_skips_a_multiline_parenthesized_expression() {
+        let source = "let x = (1 +\n  * 2\n  + 3)\nlet y = 2\nreturn y";
+        assert_eq!(titles(&compile_err(source)), ["UnexpectedToken Star"]);
+        // The statement after it is still parsed, so its own error is
+        // reported too.
+        let source = "let x = (1 +\n  * 2\n  + 3)\nlet = 2";
+        assert_eq!(
+            titles(&compile_err(source)),
+            ["UnexpectedToken Star", "UnexpectedToken Equals"]
+        );
+    }
+
+    #[test]
+    fn compound_assignment_spans_the_statement() {
+        let source = "x -= 1 + 2";
+        let Ok((ast, _)) = parse(filename(), source.to_string(), &CancelToken::new()) else {
+            panic!("Failed to parse");
+        };
+        let NodeKind::Block(statements) = &ast.kind else {
+            panic!("Parsed into {ast}");
+        };
+        assert_eq!((statements[0].span.start, statements[0].span.end), (0, 10));
+        assert_eq!(
+            tree(source),
+            "Block(1 statements) {\n  Assignment(Minus `-=`) {\n    Identifier(\"x\")\n    BinaryOperation(Plus `+`) {\n      IntegerLiteral(1)\n      IntegerLiteral(2)\n    }\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        // Tighter than comparisons, unlike C, so a mask can be compared
+        // without parentheses.
+        parses_as("a & 1 == 0", "(a & 1) == 0");
+        parses_as("a | b < c", "(a | b) < c");
+        parses_as("a | b ^ c & d", "a | (b ^ (c & d))");
+        parses_as("a & b << 1", "a & (b << 1)");
+        parses_as("a ^ b + 1", "a ^ (b + 1)");
+        parses_as("~a & b", "(~a) & b");
+        parses_as("a and b | c", "a and (b | c)");
+        assert_eq!(eval("return 6 & 3 == 2"), Value::Boolean(true));
+        assert_eq!(eval("return 1 | 2 ^ 3 & 6"), Value::Integer(1));
+    }
+
+    #[test]
+    fn unary_minus() {
+        parses_as("--x", "-(-x)");
+        parses_as("-x + 1", "(-x) + 1");
+        parses_as("-x * y", "(-x) * y");
+        assert_eq!(eval("let x = 5\nreturn -x"), Value::Integer(-5));
+        assert_eq!(eval("let x = 5\nreturn --x"), Value::Integer(5));
+        assert_eq!(eval("let x = 2.5\nreturn -x"), Value::Float(-2.5));
+        assert_eq!(eval("return -(1 + 2)"), Value::Integer(-3));
+        assert_eq!(eval("return +(1 + 2)"), Value::Integer(3));
+    }
+
+    #[test]
+    fn not_in() {
+        assert_eq!(
+            tree("x not in xs"),
+            "Block(1 statements) {\n  BinaryOperation(NotIn `not in`) {\n    Identifier(\"x\")\n    Identifier(\"xs\")\n  }\n}"
+        );
+        // Rather than `(not x) in xs`.
+        assert_ne!(tree("x not in xs"), tree("(not x) in xs"));
+        parses_as("x not in xs and y", "(x not in xs) and y");
+        parses_as("x + 1 not in xs", "(x + 1) not in xs");
+        assert_eq!(eval("return 4 not in [1, 2, 3]"), Value::Boolean(true));
+        assert_eq!(eval("return 2 not in [1, 2, 3]"), Value::Boolean(false));
+        assert_eq!(eval("return \"b\" not in \"abc\""), Value::Boolean(false));
+    }
+
+    #[test]
+    fn member_access_mixed_with_indexing() {
+        let source = "a.b[0].c";
+        assert_eq!(
+            tree(source),
+            "Block(1 statements) {\n  MemberAccess(c) {\n    Index {\n      MemberAccess(b) {\n        Identifier(\"a\")\n      }\n      IntegerLiteral(0)\n    }\n  }\n}"
+        );
+        let Ok((ast, _)) = parse(filename(), source.to_string(), &CancelToken::new()) else {
+            panic!("Failed to parse");
+        };
+        let NodeKind::Block(statements) = &ast.kind else {
+            panic!("Parsed into {ast}");
+        };
+        assert_eq!((statements[0].span.start, statements[0].span.end), (0, 8));
+        let source = "let a = {b: [{c: 1}, {c: 2}]}\nreturn a.b[1].c";
+        assert_eq!(eval(source), Value::Integer(2));
+    }
+
+    #[test]
+    fn destructuring_tree() {
+        assert_eq!(
+            tree("let [a, _, ..rest] = xs"),
+            "Block(1 statements) {\n  VarDestructure(2 targets) {\n    Binding(\"a\")\n    Wildcard\n    ..Binding(\"rest\")\n    Identifier(\"xs\")\n  }\n}"
+        );
+    }
+}
@@ -1,11 +1,7 @@
-#![allow(unused)]
 use crate::report::UnwrapReport;
 use std::fmt::{Debug, Display, Formatter};
 
 pub struct Location {
-    filename: &'static str,
-    index: usize,
-    line_index: usize,
     line: usize,
     column: usize,
 }
@@ -13,32 +9,20 @@ pub struct Location {
 impl Location {
     pub fn at(filename: &'static str, index: usize) -> Self {
         let file = crate::files::get_source(filename).unwrap_report();
-        if file.is_empty() {
-            return Self {
-                filename,
-                index,
-                line_index: 0,
-                line: 1,
-                column: 1,
-            };
-        }
-        let (line, line_index, column) = file.text()[..index].char_indices().fold(
-            (1usize, 0usize, 1usize),
-            |(line, line_index, column), (i, c)| {
+        let (line, column) = file.text()[..index]
+            .chars()
+            .fold((1, 1), |(line, column), c| {
                 if c == '\n' {
-                    (line + 1, i, 1)
+                    (line + 1, 1)
                 } else {
-                    (line, line_index, column + 1)
+                    (line, column + 1)
                 }
-            },
-        );
-        Self {
-            filename,
-            index,
-            line_index,
-            line,
-            column,
-        }
+            });
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
@@ -48,7 +32,8 @@ impl Display for Location {
     }
 }
 
-#[derive(Copy, Clone)]
+/// Ordered by file, then start, then end.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Span {
     pub filename: &'static str,
     pub start: usize,
@@ -80,9 +65,38 @@ impl Span {
         }
     }
 
-    pub fn length(&self) -> usize {
+    /// The smallest span covering both `self` and `other`, which must be
+    /// in the same file.
+    pub fn merge(&self, other: Self) -> Self {
+        debug_assert_eq!(self.filename, other.filename);
+        Self {
+            filename: self.filename,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.filename == other.filename && self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn len(&self) -> usize {
         self.end - self.start
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The source text the span covers, cut off at the end of its first line.
+    pub fn excerpt(&self) -> &'static str {
+        let source = crate::files::get_source(self.filename).unwrap_report();
+        source.text()[self.start..self.end]
+            .lines()
+            .next()
+            .unwrap_or("")
+    }
+
     pub fn start_location(&self) -> Location {
         Location::at(self.filename, self.start)
     }
@@ -92,15 +106,26 @@ impl Span {
     }
 }
 
+/// Displays as `file:line:col`, or with the alternate flag (`{:#}`) as
+/// `file:line:col-line:col` to include where the span ends. Every place a
+/// span is shown to a user goes through here.
 impl Display for Span {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.filename, self.start_location())
+        write!(f, "{}:{}", self.filename, self.start_location())?;
+        if f.alternate() {
+            write!(f, "-{}", self.end_location())?;
+        }
+        Ok(())
     }
 }
 
 impl Debug for Span {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self}-{}", self.end_location())
+        f.debug_struct("Span")
+            .field("filename", &self.filename)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
     }
 }
 
@@ -119,3 +144,81 @@ impl ariadne::Span for Span {
         self.end
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::push_source;
+    use crate::pipeline::testing::filename;
+
+    fn span(source: &str, start: usize, end: usize) -> Span {
+        let filename = filename();
+        push_source(filename, source.to_string());
+        Span::new(filename, start, end)
+    }
+
+    #[test]
+    fn display() {
+        let span = span("ab\ncd", 3, 5);
+        assert_eq!(span.to_string(), format!("{}:2:1", span.filename));
+        assert_eq!(format!("{span:#}"), format!("{}:2:1-2:3", span.filename));
+    }
+
+    #[test]
+    fn display_on_the_first_line() {
+        let span = span("let x = 1", 4, 5);
+        assert_eq!(format!("{span:#}"), format!("{}:1:5-1:6", span.filename));
+    }
+
+    #[test]
+    fn display_in_an_empty_file() {
+        let span = span("", 0, 0);
+        assert_eq!(format!("{span:#}"), format!("{}:1:1-1:1", span.filename));
+    }
+
+    #[test]
+    fn debug() {
+        let span = Span::new("main.ml", 3, 5);
+        assert_eq!(
+            format!("{span:?}"),
+            "Span { filename: \"main.ml\", start: 3, end: 5 }"
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let a = Span::new("main.ml", 2, 5);
+        let b = Span::new("main.ml", 4, 9);
+        assert_eq!(a.merge(b), Span::new("main.ml", 2, 9));
+        assert_eq!(b.merge(a), Span::new("main.ml", 2, 9));
+        let inner = Span::new("main.ml", 3, 4);
+        assert_eq!(a.merge(inner), a);
+    }
+
+    #[test]
+    fn contains() {
+        let outer = Span::new("main.ml", 2, 8);
+        assert!(outer.contains(outer));
+        assert!(outer.contains(Span::new("main.ml", 2, 3)));
+        assert!(outer.contains(Span::new("main.ml", 8, 8)));
+        assert!(!outer.contains(Span::new("main.ml", 1, 3)));
+        assert!(!outer.contains(Span::new("main.ml", 7, 9)));
+        assert!(!outer.contains(Span::new("other.ml", 3, 4)));
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(Span::new("main.ml", 2, 8).len(), 6);
+        assert_eq!(Span::at("main.ml", 4).len(), 1);
+        assert!(Span::new("main.ml", 4, 4).is_empty());
+        assert!(!Span::at("main.ml", 4).is_empty());
+    }
+
+    #[test]
+    fn excerpt() {
+        assert_eq!(span("let x = 1", 4, 9).excerpt(), "x = 1");
+        assert_eq!(span("if x {\n  y\n}", 0, 12).excerpt(), "if x {");
+        assert_eq!(span("\nx", 0, 2).excerpt(), "");
+        assert_eq!(span("let x = 1", 4, 4).excerpt(), "");
+    }
+}
@@ -3,35 +3,71 @@ use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum TokenKind {
+    Ampersand,
     And,
+    As,
     Bang,
     BangEquals,
     BooleanLiteral,
+    Break,
+    Caret,
     Colon,
+    Comma,
+    Do,
+    Dot,
+    DotDot,
+    DotDotEquals,
+    Elif,
+    Else,
     EOF,
     Equals,
+    For,
     EqualsEquals,
+    FatArrow,
     FloatLiteral,
+    GreaterGreater,
     GreaterThan,
     GreaterThanEquals,
     Identifier,
+    If,
+    Import,
+    In,
     IntegerLiteralBin,
     IntegerLiteralDec,
     IntegerLiteralHex,
     IntegerLiteralOct,
+    LeftBrace,
+    LeftBracket,
     LeftParen,
+    LessLess,
     LessThan,
     LessThanEquals,
     Let,
+    Loop,
+    Match,
     Minus,
+    MinusEquals,
+    NoneLiteral,
+    Not,
     Or,
+    Pipe,
     Plus,
+    PlusEquals,
+    QuestionQuestion,
     Return,
+    RightBrace,
+    RightBracket,
     RightParen,
     Semicolon,
     Slash,
+    SlashEquals,
+    SlashSlash,
     Star,
+    StarEquals,
     StringLiteral,
+    Then,
+    Tilde,
+    While,
 }
 
 impl Display for TokenKind {
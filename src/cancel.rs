@@ -0,0 +1,123 @@
+use crate::report::{ReportKind, ReportLevel};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Lets another thread stop parsing, compilation or execution early. Each
+/// stage checks the token between units of work and stops with a
+/// [`Cancelled`] report once it has been cancelled. Clones share state.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// A token that Ctrl-C cancels. Pressing it again, for when a stage
+    /// isn't stopping, exits straight away. If the handler can't be
+    /// installed, Ctrl-C keeps its default behaviour.
+    pub fn on_interrupt() -> Self {
+        let cancel = Self::new();
+        let handler = cancel.clone();
+        let _ = ctrlc::set_handler(move || {
+            if handler.is_cancelled() {
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+            handler.cancel();
+        });
+        cancel
+    }
+}
+
+/// The conventional status for a process killed by SIGINT.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+pub struct Cancelled;
+
+impl ReportKind for Cancelled {
+    fn title(&self) -> String {
+        "Cancelled".to_string()
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::testing::{filename, titles};
+    use crate::pipeline::{compile, compile_source, parse, run};
+    use crate::vm::{VMError, VmOptions};
+
+    const SOURCE: &str = "let x = 1\nreturn x + 1";
+
+    fn cancelled() -> CancelToken {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        cancel
+    }
+
+    #[test]
+    fn cancelled_before_parsing() {
+        let Err(diagnostics) = parse(filename(), SOURCE.to_string(), &cancelled()) else {
+            panic!("Parsed despite being cancelled");
+        };
+        assert_eq!(titles(&diagnostics), ["Cancelled"]);
+    }
+
+    #[test]
+    fn cancelled_before_compiling() {
+        let Ok((ast, _)) = parse(filename(), SOURCE.to_string(), &CancelToken::new()) else {
+            panic!("Failed to parse");
+        };
+        let Err(diagnostics) = compile(&ast, &VmOptions::default(), &cancelled()) else {
+            panic!("Compiled despite being cancelled");
+        };
+        assert_eq!(titles(&diagnostics), ["Cancelled"]);
+    }
+
+    #[test]
+    fn cancelled_before_running() {
+        let cancel = CancelToken::new();
+        let Ok((ast, _)) = parse(filename(), SOURCE.to_string(), &cancel) else {
+            panic!("Failed to parse");
+        };
+        let Ok((program, _)) = compile(&ast, &VmOptions::default(), &cancel) else {
+            panic!("Failed to compile");
+        };
+        cancel.cancel();
+        assert_eq!(
+            run(&program, &VmOptions::default(), &cancel),
+            Err(VMError::Cancelled)
+        );
+    }
+
+    #[test]
+    fn cancelled_from_another_thread() {
+        let cancel = CancelToken::new();
+        let source = "loop {}".to_string();
+        let options = VmOptions::default();
+        let Ok((program, _)) = compile_source(filename(), source, &options, &cancel) else {
+            panic!("Failed to compile");
+        };
+        let canceller = {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                cancel.cancel();
+            })
+        };
+        assert_eq!(run(&program, &options, &cancel), Err(VMError::Cancelled));
+        canceller.join().unwrap();
+    }
+}
@@ -2,7 +2,7 @@
 macro_rules! dprint {
     ($($arg:tt)*) => {
         if $crate::args::ARGS.debug() {
-            print!($($arg)*);
+            $crate::out!($($arg)*);
         };
     };
 }
@@ -11,12 +11,12 @@ macro_rules! dprint {
 macro_rules! dprintln {
     () => {
         if $crate::args::ARGS.debug() {
-            println!();
+            $crate::outln!();
         }
     };
     ($($arg:tt)*) => {
         if $crate::args::ARGS.debug() {
-            println!($($arg)*);
+            $crate::outln!($($arg)*);
         }
     };
 }
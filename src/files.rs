@@ -1,9 +1,12 @@
+use crate::eoutln;
 use crate::report::{ReportKind, ReportLevel, UnwrapReport};
 use ariadne::{Cache, Source};
 use dashmap::{DashMap, Entry};
+use std::convert::Infallible;
 use std::fmt::{Debug, Display};
 use std::fs::File;
 use std::io::{BufReader, Read, Result};
+use std::path::PathBuf;
 use std::sync::LazyLock;
 
 struct InvalidFile(String);
@@ -24,6 +27,11 @@ static CACHE: LazyLock<DashMap<&'static str, &'static Source>> = LazyLock::new(|
     hm
 });
 
+/// Sources read from disk, keyed by canonical path, so a file named by two
+/// different relative paths is only loaded once. Reports keep showing
+/// whichever name the file was referenced by.
+static CANONICAL: LazyLock<DashMap<PathBuf, &'static Source>> = LazyLock::new(DashMap::new);
+
 pub struct ScannerCache {}
 impl Cache<&'static str> for ScannerCache {
     type Storage = String;
@@ -31,12 +39,12 @@ impl Cache<&'static str> for ScannerCache {
     fn fetch(
         &mut self,
         id: &&'static str,
-    ) -> std::result::Result<&Source<Self::Storage>, Box<dyn Debug + '_>> {
-        Ok(get_source(id).unwrap_report())
+    ) -> std::result::Result<&Source<Self::Storage>, impl Debug> {
+        Ok::<_, Infallible>(get_source(id).unwrap_report())
     }
 
-    fn display<'a>(&self, id: &'a &'static str) -> Option<Box<dyn Display + 'a>> {
-        Some(Box::new(id))
+    fn display<'a>(&self, id: &'a &'static str) -> Option<impl Display + 'a> {
+        Some(id)
     }
 }
 
@@ -72,7 +80,7 @@ impl Scanner {
                     _ => self.contents.push_str(s),
                 },
                 Err(_) => {
-                    eprintln!("Failed to read from file: {}", self.filename);
+                    eoutln!("Failed to read from file: {}", self.filename);
                     std::process::exit(1);
                 }
             }
@@ -86,14 +94,20 @@ pub fn get_source(filename: &'static str) -> crate::report::Maybe<&'static Sourc
     match CACHE.entry(filename) {
         Entry::Occupied(entry) => Ok(entry.get()),
         Entry::Vacant(entry) => {
+            let canonical = std::fs::canonicalize(filename).ok();
+            if let Some(source) = canonical.as_ref().and_then(|path| CANONICAL.get(path)) {
+                return Ok(entry.insert(*source).value_mut());
+            }
             let contents = Scanner::new(filename)
                 .map_err(|e| InvalidFile(filename.to_string()).make().with_note(e))?
                 .read()
                 .map_err(|e| InvalidFile(filename.to_string()).make().with_note(e))?
                 .contents;
-            Ok(entry
-                .insert(Box::leak(Source::from(contents).into()))
-                .value_mut())
+            let source: &'static Source = Box::leak(Source::from(contents).into());
+            if let Some(path) = canonical {
+                CANONICAL.insert(path, source);
+            }
+            Ok(entry.insert(source).value_mut())
         }
     }
 }
@@ -101,3 +115,22 @@ pub fn get_source(filename: &'static str) -> crate::report::Maybe<&'static Sourc
 pub fn push_source(filename: &'static str, source: String) {
     CACHE.insert(filename, Box::leak(Source::from(source).into()));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::testing::{path_name, temp_dir};
+
+    #[test]
+    fn sources_are_shared_by_canonical_path() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("x.ml"), "return 1").unwrap();
+        let plain = path_name(dir.join("x.ml"));
+        let dotted = path_name(dir.join(".").join("x.ml"));
+        let (Ok(first), Ok(second)) = (get_source(plain), get_source(dotted)) else {
+            panic!("Failed to read {plain}");
+        };
+        assert!(std::ptr::eq(first, second));
+        assert_eq!(first.text(), "return 1");
+    }
+}
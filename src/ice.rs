@@ -0,0 +1,82 @@
+use crate::dprintln;
+use crate::report::{ReportConfig, ReportKind, ReportLevel};
+use std::backtrace::Backtrace;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::exit;
+use std::sync::Mutex;
+
+pub const ICE_EXIT_CODE: i32 = 101;
+
+/// Set to the name of a phase, e.g. "compilation", to panic at its start.
+/// It's undocumented, and only there to test the reports made here.
+const FORCE_ICE_VAR: &str = "MOONLITE_FORCE_ICE";
+
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
+
+struct InternalCompilerError {
+    filename: &'static str,
+    phase: &'static str,
+}
+
+impl ReportKind for InternalCompilerError {
+    fn title(&self) -> String {
+        format!(
+            "Internal compiler error during {} of {:?}",
+            self.phase, self.filename
+        )
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Bug
+    }
+}
+
+/// Replaces the default panic output with a recorded message, which
+/// [`guard`] later renders as an internal compiler error report.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        let message = match info.location() {
+            Some(location) => format!("'{message}' at {location}"),
+            None => format!("'{message}'"),
+        };
+        dprintln!("{}", Backtrace::force_capture());
+        if let Ok(mut slot) = PANIC_MESSAGE.lock() {
+            *slot = Some(message);
+        }
+    }));
+}
+
+/// Runs one phase of the pipeline, turning a panic inside it into an
+/// internal compiler error report and exiting with [`ICE_EXIT_CODE`].
+pub fn guard<T, F: FnOnce() -> T>(filename: &'static str, phase: &'static str, f: F) -> T {
+    let f = || {
+        if std::env::var(FORCE_ICE_VAR).is_ok_and(|forced| forced == phase) {
+            panic!("Forced by {FORCE_ICE_VAR}");
+        }
+        f()
+    };
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(val) => val,
+        Err(_) => {
+            let message = PANIC_MESSAGE
+                .lock()
+                .ok()
+                .and_then(|mut slot| slot.take())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            InternalCompilerError { filename, phase }
+                .make()
+                .with_note(format!("panicked with {message}"))
+                .with_help("This is a bug in moonlite. Please file an issue with the output of --debug attached")
+                .finish()
+                .eprint(ReportConfig::default());
+            exit(ICE_EXIT_CODE);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! The moonlite interpreter, as a library. Tools that want each stage's
+//! output and reports as values should start at [`pipeline`]; the binary
+//! drives the stages itself, rendering reports and exiting on errors.
+#![allow(clippy::upper_case_acronyms)]
+#![warn(clippy::complexity)]
+
+pub mod args;
+pub mod ast;
+pub mod cancel;
+pub mod debug;
+pub mod files;
+pub mod ice;
+pub mod output;
+pub mod pipeline;
+pub mod report;
+pub mod stats;
+pub mod types;
+pub mod vm;
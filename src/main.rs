@@ -1,40 +1,65 @@
 #![allow(clippy::upper_case_acronyms)]
 #![warn(clippy::complexity)]
 
-mod args;
-mod ast;
-mod debug;
-mod files;
-mod report;
-mod types;
-mod vm;
-
-use crate::args::ARGS;
-use crate::ast::parser::Parser;
-use crate::report::{ReportChannel, UnwrapReport};
-use crate::vm::{Compiler, VM};
+use moonlite::args::ARGS;
+use moonlite::ast::parser::Parser;
+use moonlite::cancel::CancelToken;
+use moonlite::dprintln;
+use moonlite::ice;
+use moonlite::report::{ReportBuilder, ReportChannel, UnwrapReport};
+use moonlite::stats::Stats;
+use moonlite::vm::{Compiler, VmOptions, VM};
 
 fn main() {
     let mut report_channel = ReportChannel::new();
+    let mut stats = Stats::new();
+    ice::install_hook();
+    let options = VmOptions::from_args();
+    let cancel = CancelToken::on_interrupt();
     if let Some(filename) = ARGS.input() {
         let sender = report_channel.get_sender();
-        let mut parser = Parser::new(filename, sender).unwrap_report();
-        let ast = parser.parse();
+        let ast = stats.time("parse", || {
+            ice::guard(filename, "parsing", || {
+                let mut parser = Parser::new(filename, sender)
+                    .unwrap_report()
+                    .with_cancel(cancel.clone());
+                parser.parse()
+            })
+        });
         dprintln!("{:#?}", ast);
+        stats.record_ast(&ast);
 
         report_channel.check_reports_and_exit();
 
-        let mut chunk = {
-            let mut compiler = Compiler::new();
-            compiler.compile_program(&ast);
-            compiler.chunk
-        };
+        let sender = report_channel.get_sender();
+        let program = stats.time("compile", || {
+            ice::guard(filename, "compilation", || {
+                let mut compiler = Compiler::new(sender)
+                    .with_options(&options)
+                    .with_cancel(cancel.clone());
+                compiler.compile_program(&ast);
+                compiler.program
+            })
+        });
+        stats.record_program(&program);
 
-        report_channel.check_reports();
+        report_channel.check_reports_and_exit();
 
-        let mut vm = VM::new(&mut chunk);
-        let val = vm.run().unwrap_report();
+        let val = stats.time("run", || {
+            ice::guard(filename, "execution", || {
+                let mut vm = VM::new(&program)
+                    .with_options(options.clone())
+                    .with_cancel(cancel.clone());
+                let result = vm.run();
+                vm.print_profile();
+                result.map_err(Box::<ReportBuilder>::from).unwrap_report()
+            })
+        });
         dprintln!("Return Value: {:?}", val);
+
+        if ARGS.time() {
+            stats.print();
+        }
     } else if ARGS.input().is_none() {
         // Repl::new(&reporter).start_loop()
         unimplemented!("No repl yet. Please provide a file with --input");
@@ -0,0 +1,65 @@
+//! Every write to stdout and stderr goes through here, so that output
+//! piped into something like `head` ends the process quietly when the
+//! reader goes away, rather than panicking on the first failed write.
+use std::fmt::Arguments;
+use std::io;
+use std::io::Write;
+use std::process::exit;
+
+/// The status a shell reports for a process killed by `SIGPIPE`.
+pub const BROKEN_PIPE_EXIT_CODE: i32 = 141;
+
+/// Exits on a failed write: silently for a closed pipe, and with a single
+/// message for any other error.
+pub fn check(result: io::Result<()>) {
+    match result {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => exit(BROKEN_PIPE_EXIT_CODE),
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "moonlite: failed to write output: {err}");
+            exit(1);
+        }
+    }
+}
+
+pub fn stdout(args: Arguments) {
+    check(io::stdout().write_fmt(args));
+}
+
+pub fn stderr(args: Arguments) {
+    check(io::stderr().write_fmt(args));
+}
+
+#[macro_export]
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::stdout(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::output::stdout(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+#[macro_export]
+macro_rules! eout {
+    ($($arg:tt)*) => {
+        $crate::output::stderr(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! eoutln {
+    () => {
+        $crate::output::stderr(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::stderr(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
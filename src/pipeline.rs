@@ -0,0 +1,285 @@
+//! Entry points for tools that want each stage's output and reports as
+//! values, rather than having reports rendered and the process exit.
+//!
+//! The binary drives each stage itself so it can time them, rather than
+//! going through here.
+use crate::ast::parser::Parser;
+use crate::ast::Node;
+use crate::cancel::CancelToken;
+use crate::files::push_source;
+use crate::report::{Diagnostics, ReportCollector};
+use crate::vm::{Compiler, Program, VMError, VMResult, Value, VmOptions, VM};
+
+/// The output of a stage along with any warnings it produced, or every
+/// report if it produced an error.
+pub type Diagnosed<T> = Result<(T, Diagnostics), Diagnostics>;
+
+fn diagnosed<T>(value: T, collector: ReportCollector) -> Diagnosed<T> {
+    let diagnostics = Diagnostics::new(collector.take());
+    if diagnostics.is_err() {
+        Err(diagnostics)
+    } else {
+        Ok((value, diagnostics))
+    }
+}
+
+/// Parses `source` under the name `filename`.
+pub fn parse(filename: &'static str, source: String, cancel: &CancelToken) -> Diagnosed<Box<Node>> {
+    push_source(filename, source);
+    let collector = ReportCollector::new();
+    let mut parser = Parser::new(filename, collector.clone())?.with_cancel(cancel.clone());
+    let ast = parser.parse();
+    diagnosed(ast, collector)
+}
+
+/// Compiles a parsed program. Parse errors should be checked first, since
+/// the compiler assumes the tree it's given is well formed.
+pub fn compile(program: &Node, options: &VmOptions, cancel: &CancelToken) -> Diagnosed<Program> {
+    let collector = ReportCollector::new();
+    let mut compiler = Compiler::new(collector.clone())
+        .with_options(options)
+        .with_cancel(cancel.clone());
+    compiler.compile_program(program);
+    diagnosed(compiler.program, collector)
+}
+
+/// Parses and then compiles `source`, stopping after parsing if it
+/// produced errors.
+pub fn compile_source(
+    filename: &'static str,
+    source: String,
+    options: &VmOptions,
+    cancel: &CancelToken,
+) -> Diagnosed<Program> {
+    let (ast, mut diagnostics) = parse(filename, source, cancel)?;
+    match compile(&ast, options, cancel) {
+        Ok((program, compile_diagnostics)) => {
+            diagnostics.extend(compile_diagnostics);
+            Ok((program, diagnostics))
+        }
+        Err(compile_diagnostics) => {
+            diagnostics.extend(compile_diagnostics);
+            Err(diagnostics)
+        }
+    }
+}
+
+/// Runs a compiled program. A runtime error is returned as it is, so the
+/// caller can tell what went wrong by its variant rather than its message.
+pub fn run(program: &Program, options: &VmOptions, cancel: &CancelToken) -> VMResult<Value> {
+    VM::new(program)
+        .with_options(options.clone())
+        .with_cancel(cancel.clone())
+        .run()
+}
+
+/// Why [`run_source`] didn't produce a value.
+pub enum SourceError {
+    /// Parsing or compiling failed, with every report either produced.
+    Diagnostics(Diagnostics),
+    /// The program failed while running.
+    Runtime(VMError),
+}
+
+/// Parses, compiles and runs `source` under the name `filename`.
+pub fn run_source(
+    filename: &'static str,
+    source: String,
+    options: &VmOptions,
+    cancel: &CancelToken,
+) -> Result<Value, SourceError> {
+    let (program, _) =
+        compile_source(filename, source, options, cancel).map_err(SourceError::Diagnostics)?;
+    run(&program, options, cancel).map_err(SourceError::Runtime)
+}
+
+/// Helpers for tests that run snippets of source through the pipeline.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use crate::report::ReportConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A filename no other test uses, since sources are cached by name.
+    pub fn filename() -> &'static str {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        format!("test{}.ml", NEXT.fetch_add(1, Ordering::Relaxed)).leak()
+    }
+
+    /// An empty directory no other test uses, for tests that need files.
+    pub fn temp_dir() -> std::path::PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "moonlite-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `path` as a filename the pipeline can take.
+    pub fn path_name(path: std::path::PathBuf) -> &'static str {
+        path.to_string_lossy().into_owned().leak()
+    }
+
+    fn render(diagnostics: &Diagnostics) -> String {
+        diagnostics.render(ReportConfig {
+            compact: true,
+            context: false,
+        })
+    }
+
+    /// Compiles `source`, returning the warnings and advice it produced.
+    /// Panics with the reports if it has errors.
+    pub fn compile(source: &str) -> Diagnostics {
+        match compile_source(
+            filename(),
+            source.to_string(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Ok((_, diagnostics)) => diagnostics,
+            Err(diagnostics) => panic!("Failed to compile:\n{}", render(&diagnostics)),
+        }
+    }
+
+    /// Compiles `source`, returning every report. Panics if it has no
+    /// errors.
+    pub fn compile_err(source: &str) -> Diagnostics {
+        match compile_source(
+            filename(),
+            source.to_string(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Ok(_) => panic!("Compiled without errors"),
+            Err(diagnostics) => diagnostics,
+        }
+    }
+
+    /// Parses `source`, returning its tree as [`Node`] displays it, but
+    /// without the spans. Panics if it has errors.
+    pub fn tree(source: &str) -> String {
+        let filename = filename();
+        let ast = match parse(filename, source.to_string(), &CancelToken::new()) {
+            Ok((ast, _)) => ast,
+            Err(diagnostics) => panic!("Failed to parse:\n{}", render(&diagnostics)),
+        };
+        let mut tree = ast.to_string();
+        let span = format!("[{filename}:");
+        while let Some(start) = tree.find(&span) {
+            let end = start + tree[start..].find(']').unwrap();
+            tree.replace_range(start..=end, "");
+        }
+        tree
+    }
+
+    /// The titles of `diagnostics`' reports, in order.
+    pub fn titles(diagnostics: &Diagnostics) -> Vec<&str> {
+        diagnostics
+            .reports
+            .iter()
+            .map(|report| report.title())
+            .collect()
+    }
+
+    /// Runs `source`, returning the value of its `return`, or the runtime
+    /// error it stopped with. Panics if it doesn't compile.
+    pub fn run(source: &str) -> VMResult<Value> {
+        match run_source(
+            filename(),
+            source.to_string(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Ok(value) => Ok(value),
+            Err(SourceError::Runtime(err)) => Err(err),
+            Err(SourceError::Diagnostics(diagnostics)) => {
+                panic!("Failed to compile:\n{}", render(&diagnostics))
+            }
+        }
+    }
+
+    /// Runs `source`, returning the value of its `return`. Panics if it
+    /// fails.
+    pub fn eval(source: &str) -> Value {
+        run(source).unwrap_or_else(|err| panic!("Failed to run: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{filename, titles};
+    use super::*;
+
+    #[test]
+    fn parse_errors_stop_before_compiling() {
+        let source = "return nope +".to_string();
+        let Err(diagnostics) = compile_source(
+            filename(),
+            source,
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) else {
+            panic!("Compiled without errors");
+        };
+        assert_eq!(titles(&diagnostics), ["UnexpectedEOF"]);
+    }
+
+    #[test]
+    fn compile_errors_stop_before_running() {
+        let source = "return nope".to_string();
+        match run_source(
+            filename(),
+            source,
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Err(SourceError::Diagnostics(diagnostics)) => {
+                assert_eq!(titles(&diagnostics), ["UndefinedVariable `nope`"])
+            }
+            _ => panic!("Expected compile errors"),
+        }
+    }
+
+    #[test]
+    fn runtime_errors_are_returned_as_values() {
+        let source = "return 1 // 0".to_string();
+        match run_source(
+            filename(),
+            source,
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Err(SourceError::Runtime(VMError::DivisionByZero { .. })) => (),
+            _ => panic!("Expected a runtime error"),
+        }
+    }
+
+    #[test]
+    fn every_parse_error_is_returned() {
+        let source = "let = 1\nlet y = )\nreturn \"\\q\"\n";
+        let Err(diagnostics) = parse(filename(), source.to_string(), &CancelToken::new()) else {
+            panic!("Parsed without errors");
+        };
+        assert_eq!(
+            titles(&diagnostics),
+            [
+                "UnexpectedToken Equals",
+                "UnexpectedToken RightParen",
+                "SyntaxError Invalid Escape Character: q",
+            ]
+        );
+        assert_eq!((diagnostics.errors, diagnostics.warnings), (3, 0));
+        let Err(diagnostics) = compile_source(
+            filename(),
+            source.to_string(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) else {
+            panic!("Compiled without errors");
+        };
+        assert_eq!(diagnostics.errors, 3);
+    }
+}
@@ -2,15 +2,22 @@
 use crate::args::ARGS;
 use crate::ast::span::Span;
 use crate::files::ScannerCache;
+use crate::{eoutln, output};
 use ariadne::{Color, Config};
 use name_variant::NamedVariant;
 use owo_colors::colors::CustomColor;
-use owo_colors::{AnsiColors, OwoColorize};
+use owo_colors::OwoColorize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::fmt::Display;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::process::exit;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
 
 pub type Maybe<T> = Result<T, Box<ReportBuilder>>;
 pub type MaybeFinal<T> = Result<T, Box<Report>>;
@@ -112,12 +119,27 @@ impl<T> UnwrapReport<T> for Maybe<T> {
     }
 }
 
+impl<T> UnwrapReport<T> for Result<T, Diagnostics> {
+    fn unwrap_report(self) -> T {
+        match self {
+            Ok(val) => val,
+            Err(diagnostics) => {
+                for report in diagnostics.reports {
+                    ReportChannel::should_display(&report)
+                        .then(|| report.eprint(ReportConfig::default()));
+                }
+                exit(1);
+            }
+        }
+    }
+}
+
 impl<T> UnwrapReport<T> for MaybeFinal<T> {
     fn unwrap_report(self) -> T {
         match self {
             Ok(val) => val,
             Err(err) => {
-                ReportChannel::should_display(&*err).then(|| err.eprint(ReportConfig::default()));
+                ReportChannel::should_display(&err).then(|| err.eprint(ReportConfig::default()));
                 exit(1);
             }
         }
@@ -138,6 +160,7 @@ where
             help: None,
             note: None,
             labels: Vec::new(),
+            group_id: None,
         }
     }
 
@@ -146,21 +169,58 @@ where
     }
 }
 
+/// Ordered from most to least severe. `Silent` is only meaningful as a
+/// `--report-level` threshold, so a report at that level is never shown.
 #[derive(NamedVariant, Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub enum ReportLevel {
+    /// A bug in moonlite itself. Always shown, whatever the report level.
+    Bug,
     Silent,
     Error,
     Warn,
     Advice,
+    /// Extra context for the report sharing its group, shown along with it.
+    Note,
+}
+
+impl ReportLevel {
+    /// Whether a report at this level makes the run fail.
+    pub fn is_error(self) -> bool {
+        matches!(self, ReportLevel::Bug | ReportLevel::Error)
+    }
+
+    /// Whether a report at this level is shown with `--report-level` set
+    /// to `threshold`.
+    pub fn is_shown_at(self, threshold: ReportLevel) -> bool {
+        match self {
+            ReportLevel::Bug => true,
+            ReportLevel::Silent => false,
+            ReportLevel::Note => threshold >= ReportLevel::Advice,
+            level => threshold >= level,
+        }
+    }
+
+    /// Colors `text` for this level in the non-ariadne renderer.
+    fn paint(self, text: String) -> String {
+        match self {
+            ReportLevel::Bug => text.magenta().bold().to_string(),
+            ReportLevel::Error => text.red().to_string(),
+            ReportLevel::Warn => text.yellow().to_string(),
+            ReportLevel::Advice => text.blue().to_string(),
+            ReportLevel::Note | ReportLevel::Silent => text.dimmed().to_string(),
+        }
+    }
 }
 
 impl From<ReportLevel> for ariadne::ReportKind<'_> {
     fn from(value: ReportLevel) -> Self {
         match value {
+            ReportLevel::Bug => Self::Custom("Bug", value.into()),
             ReportLevel::Error => Self::Error,
             ReportLevel::Warn => Self::Warning,
             ReportLevel::Advice => Self::Advice,
-            ReportLevel::Silent => panic!("Turned SILENT into report kind"),
+            ReportLevel::Note => Self::Custom("Note", value.into()),
+            ReportLevel::Silent => Self::Custom("Silent", value.into()),
         }
     }
 }
@@ -168,14 +228,22 @@ impl From<ReportLevel> for ariadne::ReportKind<'_> {
 impl From<ReportLevel> for Color {
     fn from(value: ReportLevel) -> Self {
         match value {
-            ReportLevel::Advice => Color::BrightBlue,
-            ReportLevel::Warn => Color::Yellow,
+            ReportLevel::Bug => Color::Magenta,
             ReportLevel::Error => Color::Red,
-            ReportLevel::Silent => panic!("Turned SILENT into color"),
+            ReportLevel::Warn => Color::Yellow,
+            ReportLevel::Advice => Color::BrightBlue,
+            ReportLevel::Note | ReportLevel::Silent => Color::BrightBlack,
         }
     }
 }
 
+/// Returns an id that hasn't been handed out before, for tying a
+/// [`ReportLevel::Note`] to the report it belongs with.
+pub fn new_group_id() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
 #[must_use]
 pub struct ReportBuilder {
     pub level: ReportLevel,
@@ -183,6 +251,7 @@ pub struct ReportBuilder {
     pub help: Option<String>,
     pub note: Option<String>,
     pub labels: Vec<Label>,
+    pub group_id: Option<usize>,
 }
 
 impl ReportBuilder {
@@ -216,6 +285,16 @@ impl ReportBuilder {
         self
     }
 
+    pub fn set_group(&mut self, group_id: usize) -> &mut Self {
+        self.group_id = Some(group_id);
+        self
+    }
+
+    pub fn with_group(mut self, group_id: usize) -> Self {
+        self.set_group(group_id);
+        self
+    }
+
     pub fn finish(self) -> Report {
         Report {
             level: self.level,
@@ -223,6 +302,7 @@ impl ReportBuilder {
             help: self.help,
             note: self.note,
             labels: self.labels,
+            group_id: self.group_id,
         }
     }
 }
@@ -249,9 +329,35 @@ pub struct Report {
     help: Option<String>,
     note: Option<String>,
     labels: Vec<Label>,
+    /// Reports sharing a group are displayed together. A `Note` in a group
+    /// is only shown if the report before it in that group was.
+    pub group_id: Option<usize>,
 }
 
 impl Report {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The span and message of each label, in order.
+    #[cfg(test)]
+    pub fn labels(&self) -> Vec<(Span, Option<&str>)> {
+        self.labels
+            .iter()
+            .map(|label| (label.span, label.message.as_deref()))
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    #[cfg(test)]
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
     fn into_ariadne_report(self) -> ariadne::Report<'static, Span> {
         let mut builder = ariadne::Report::build(
             self.level.into(),
@@ -276,19 +382,16 @@ impl Report {
         builder.finish()
     }
 
-    pub fn write<W: Write>(self, mut dst: W, config: ReportConfig) {
+    pub fn write<W: Write>(self, mut dst: W, config: ReportConfig) -> io::Result<()> {
         if !config.compact && (config.context && !self.labels.is_empty()) {
-            self.into_ariadne_report()
-                .write(ScannerCache {}, dst)
-                .expect("Failed to write error via ariadne.");
-            return;
+            return self.into_ariadne_report().write(ScannerCache {}, dst);
         }
 
         let compact_span = config.compact || (self.note.is_none() && self.help.is_none());
         writeln!(
             dst,
             "{} {}",
-            format!(
+            self.level.paint(format!(
                 "{}{}:",
                 if compact_span && !self.labels.is_empty() {
                     format!("[{}] ", self.labels.first().unwrap().span)
@@ -296,24 +399,18 @@ impl Report {
                     "".to_string()
                 },
                 self.level.variant_name()
-            )
-            .color(match self.level {
-                ReportLevel::Advice => AnsiColors::Blue,
-                ReportLevel::Warn => AnsiColors::Yellow,
-                ReportLevel::Error => AnsiColors::Red,
-                ReportLevel::Silent => unreachable!(),
-            }),
+            )),
             self.title
-        );
+        )?;
         if config.compact {
-            return;
+            return Ok(());
         }
         if !compact_span && !self.labels.is_empty() {
-            write!(dst, "  ");
+            write!(dst, "  ")?;
             if !config.compact && (self.help.is_some() || self.note.is_some()) {
-                write!(dst, "{}", "╭─".bright_black());
+                write!(dst, "{}", "╭─".bright_black())?;
             }
-            writeln!(dst, "[{}] ", self.labels.first().unwrap().span);
+            writeln!(dst, "[{}] ", self.labels.first().unwrap().span)?;
         }
         if let Some(help) = self.help {
             writeln!(
@@ -322,7 +419,7 @@ impl Report {
                 "│".bright_black(),
                 "Help".fg::<CustomColor<132, 209, 172>>(),
                 help
-            );
+            )?;
         }
         if let Some(note) = self.note {
             writeln!(
@@ -331,16 +428,65 @@ impl Report {
                 "│".bright_black(),
                 "Note".fg::<CustomColor<132, 209, 172>>(),
                 note
-            );
+            )?;
         }
+        Ok(())
     }
 
     pub fn print(self, config: ReportConfig) {
-        self.write(io::stdout(), config);
+        output::check(self.write(io::stdout(), config));
     }
 
     pub fn eprint(self, config: ReportConfig) {
-        self.write(io::stderr(), config);
+        output::check(self.write(io::stderr(), config));
+    }
+}
+
+/// Every report produced by a pipeline stage, for library callers.
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+    pub reports: Vec<Report>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl Diagnostics {
+    pub fn new(reports: Vec<Report>) -> Self {
+        let errors = reports.iter().filter(|r| r.level.is_error()).count();
+        let warnings = reports
+            .iter()
+            .filter(|r| r.level == ReportLevel::Warn)
+            .count();
+        Self {
+            reports,
+            errors,
+            warnings,
+        }
+    }
+
+    pub fn is_err(&self) -> bool {
+        self.errors > 0
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.reports.extend(other.reports);
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+    }
+
+    /// Renders every report, in order, without any level filtering.
+    pub fn render(&self, config: ReportConfig) -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        for report in &self.reports {
+            output::check(report.clone().write(&mut buffer, config));
+        }
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+impl From<Box<ReportBuilder>> for Diagnostics {
+    fn from(value: Box<ReportBuilder>) -> Self {
+        Self::new(vec![value.finish()])
     }
 }
 
@@ -349,30 +495,168 @@ pub enum ExitStatus {
     Yes,
 }
 
+enum ChannelMessage {
+    Report(Box<Report>),
+    Flush(Sender<()>),
+}
+
+#[derive(Default)]
+struct ReportCounters {
+    reported: AtomicUsize,
+    errors: AtomicUsize,
+    /// Reports that would have been shown if not for `--max-reports`.
+    suppressed: AtomicUsize,
+}
+
+enum ChannelMode {
+    Batch(Receiver<ChannelMessage>),
+    Streaming,
+}
+
+/// Collects reports from any number of [`ReportSender`]s.
+///
+/// When stderr is a terminal, reports are rendered by a background thread
+/// as soon as they arrive, in the order the channel receives them. Reports
+/// sent from one sender keep their relative order, and [`check_reports`]
+/// waits until every report sent before the call has been rendered.
+/// Otherwise reports are buffered and printed together by `check_reports`,
+/// which keeps output deterministic for CI and piped runs.
+///
+/// [`check_reports`]: ReportChannel::check_reports
 pub struct ReportChannel {
-    reported: usize,
-    pub sender: Sender<Box<Report>>,
-    pub receiver: Receiver<Box<Report>>,
+    counters: Arc<ReportCounters>,
+    sender: Sender<ChannelMessage>,
+    mode: ChannelMode,
+}
+
+/// Somewhere for a pipeline stage to put the reports it produces.
+pub trait ReportSink {
+    fn report(&self, report: Box<Report>);
+}
+
+/// Lets a stage hand its sink on to the stages it starts itself.
+impl<T: ReportSink + ?Sized> ReportSink for Rc<T> {
+    fn report(&self, report: Box<Report>) {
+        (**self).report(report);
+    }
 }
 
 #[derive(Clone)]
 pub struct ReportSender {
-    sender: Sender<Box<Report>>,
+    sender: Sender<ChannelMessage>,
+}
+
+impl ReportSink for ReportSender {
+    fn report(&self, report: Box<Report>) {
+        self.sender
+            .send(ChannelMessage::Report(report))
+            .expect("Failed to send report");
+    }
+}
+
+/// Keeps reports as values instead of rendering them, for callers that
+/// want to decide how to display them. Clones share the same storage.
+#[derive(Clone, Default)]
+pub struct ReportCollector {
+    reports: Rc<RefCell<Vec<Report>>>,
+}
+
+impl ReportCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take(&self) -> Vec<Report> {
+        std::mem::take(&mut self.reports.borrow_mut())
+    }
+}
+
+impl ReportSink for ReportCollector {
+    fn report(&self, report: Box<Report>) {
+        self.reports.borrow_mut().push(*report);
+    }
 }
 
-impl ReportSender {
-    pub fn report(&self, report: Box<Report>) {
-        self.sender.send(report).expect("Failed to send report");
+impl Default for ReportChannel {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl ReportChannel {
+    /// Number of recently rendered reports remembered for deduplication
+    /// while streaming.
+    const DEDUP_WINDOW: usize = 8;
+
     pub fn new() -> ReportChannel {
+        if io::stderr().is_terminal() {
+            Self::streaming()
+        } else {
+            Self::batch()
+        }
+    }
+
+    pub fn batch() -> ReportChannel {
         let (sender, receiver) = std::sync::mpsc::channel();
         ReportChannel {
-            reported: 0,
+            counters: Arc::default(),
             sender,
-            receiver,
+            mode: ChannelMode::Batch(receiver),
+        }
+    }
+
+    pub fn streaming() -> ReportChannel {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let counters: Arc<ReportCounters> = Arc::default();
+        let drain_counters = counters.clone();
+        thread::spawn(move || Self::drain(receiver, &drain_counters));
+        ReportChannel {
+            counters,
+            sender,
+            mode: ChannelMode::Streaming,
+        }
+    }
+
+    fn drain(receiver: Receiver<ChannelMessage>, counters: &ReportCounters) {
+        let config = ReportConfig::default();
+        let mut recent: VecDeque<Vec<u8>> = VecDeque::with_capacity(Self::DEDUP_WINDOW);
+        let mut shown_group = None;
+        for message in receiver {
+            let report = match message {
+                ChannelMessage::Report(report) => report,
+                ChannelMessage::Flush(ack) => {
+                    let _ = ack.send(());
+                    continue;
+                }
+            };
+            if report.level.is_error() {
+                counters.errors.fetch_add(1, Ordering::SeqCst);
+            }
+            let is_note = report.level == ReportLevel::Note;
+            if !Self::admit(&report, counters, shown_group) {
+                if !is_note {
+                    shown_group = None;
+                }
+                continue;
+            }
+            let group_id = report.group_id;
+            let mut buffer: Vec<u8> = Vec::new();
+            output::check(report.write(&mut buffer, config));
+            if recent.contains(&buffer) {
+                if !is_note {
+                    shown_group = None;
+                }
+                continue;
+            }
+            output::check(io::stderr().write_all(&buffer));
+            if !is_note {
+                counters.reported.fetch_add(1, Ordering::SeqCst);
+                shown_group = group_id;
+            }
+            if recent.len() == Self::DEDUP_WINDOW {
+                recent.pop_front();
+            }
+            recent.push_back(buffer);
         }
     }
 
@@ -383,28 +667,95 @@ impl ReportChannel {
     }
 
     pub fn should_display(report: &Report) -> bool {
-        ARGS.report_level.to_value() >= report.level
+        report.level.is_shown_at(ARGS.report_level.to_value())
+    }
+
+    /// Whether `report` should be rendered, given how many reports have
+    /// been and the group of the last one that was. A grouped note follows
+    /// its report and doesn't count towards `--max-reports`, while a bug is
+    /// shown even past the limit.
+    fn admit(report: &Report, counters: &ReportCounters, shown_group: Option<usize>) -> bool {
+        match (report.level, report.group_id) {
+            (ReportLevel::Note, Some(group_id)) => shown_group == Some(group_id),
+            (ReportLevel::Bug, _) => true,
+            _ if !Self::should_display(report) => false,
+            _ if counters.reported.load(Ordering::SeqCst) < ARGS.max_reports() => true,
+            _ => {
+                counters.suppressed.fetch_add(1, Ordering::SeqCst);
+                false
+            }
+        }
     }
 
-    pub fn check_reports(&mut self) -> ExitStatus {
+    /// Picks out the reports the batch mode renders, in order, along with
+    /// the number of errors among all of them.
+    fn admit_batch(
+        reports: impl IntoIterator<Item = Box<Report>>,
+        counters: &ReportCounters,
+    ) -> (Vec<Report>, usize) {
+        let mut admitted = Vec::new();
         let mut errors = 0usize;
-        let mut buffer: Vec<u8> = Vec::new();
-        let config = ReportConfig::default();
-        for report in self.receiver.try_iter() {
-            if report.level == ReportLevel::Error {
+        let mut shown_group = None;
+        for report in reports {
+            if report.level.is_error() {
                 errors += 1;
             }
-            if !Self::should_display(&*report) || self.reported == ARGS.max_reports() {
+            let is_note = report.level == ReportLevel::Note;
+            let admit = Self::admit(&report, counters, shown_group);
+            if !is_note {
+                shown_group = report.group_id.filter(|_| admit);
+            }
+            if !admit {
                 continue;
             }
-            report.write(&mut buffer, config);
-            self.reported += 1;
+            if !is_note {
+                counters.reported.fetch_add(1, Ordering::SeqCst);
+            }
+            admitted.push(*report);
+        }
+        (admitted, errors)
+    }
+
+    pub fn check_reports(&mut self) -> ExitStatus {
+        let mut buffer: Vec<u8> = Vec::new();
+        let errors = match &self.mode {
+            ChannelMode::Batch(receiver) => {
+                let reports = receiver.try_iter().filter_map(|message| match message {
+                    ChannelMessage::Report(report) => Some(report),
+                    ChannelMessage::Flush(_) => None,
+                });
+                let (admitted, errors) = Self::admit_batch(reports, &self.counters);
+                let config = ReportConfig::default();
+                for report in admitted {
+                    output::check(report.write(&mut buffer, config));
+                }
+                errors
+            }
+            ChannelMode::Streaming => {
+                let (ack, done) = std::sync::mpsc::channel();
+                self.sender
+                    .send(ChannelMessage::Flush(ack))
+                    .expect("Failed to flush reports");
+                done.recv().expect("Report thread stopped unexpectedly");
+                self.counters.errors.swap(0, Ordering::SeqCst)
+            }
+        };
+        output::check(io::stderr().write_all(&buffer));
+        let suppressed = self.counters.suppressed.swap(0, Ordering::SeqCst);
+        if suppressed > 0 {
+            eoutln!(
+                "{}",
+                format_args!(
+                    "{suppressed} more reports hidden by --max-reports {}.",
+                    ARGS.max_reports()
+                )
+                .dimmed()
+            );
         }
         if errors > 0 {
             if ARGS.report_level.to_value() != ReportLevel::Silent {
-                eprintln!(
-                    "{}{}",
-                    std::str::from_utf8(&buffer).unwrap(),
+                eoutln!(
+                    "{}",
                     format_args!("Failed with {errors} errors emitted.").red()
                 );
             }
@@ -421,3 +772,89 @@ impl ReportChannel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(level: ReportLevel, title: &str, group_id: Option<usize>) -> Box<Report> {
+        Box::new(
+            ReportBuilder {
+                level,
+                title: title.to_string(),
+                help: None,
+                note: None,
+                labels: Vec::new(),
+                group_id,
+            }
+            .finish(),
+        )
+    }
+
+    fn admitted_titles(
+        reports: impl IntoIterator<Item = Box<Report>>,
+        counters: &ReportCounters,
+    ) -> Vec<String> {
+        let (admitted, _) = ReportChannel::admit_batch(reports, counters);
+        admitted
+            .into_iter()
+            .map(|report| report.title().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn notes_follow_their_group() {
+        let (shown, hidden) = (new_group_id(), new_group_id());
+        let reports = vec![
+            report(ReportLevel::Error, "error", Some(shown)),
+            report(ReportLevel::Note, "shown note", Some(shown)),
+            report(ReportLevel::Advice, "advice", Some(hidden)),
+            report(ReportLevel::Note, "hidden note", Some(hidden)),
+            report(ReportLevel::Warn, "warning", None),
+            report(ReportLevel::Note, "late note", Some(shown)),
+        ];
+        // Advice is below the default report level, so its note goes too,
+        // and a note only follows the report directly before it.
+        assert_eq!(
+            admitted_titles(reports, &ReportCounters::default()),
+            ["error", "shown note", "warning"]
+        );
+    }
+
+    #[test]
+    fn notes_dont_count_towards_max_reports() {
+        let counters = ReportCounters::default();
+        let group = new_group_id();
+        let reports = vec![
+            report(ReportLevel::Error, "error", Some(group)),
+            report(ReportLevel::Note, "note", Some(group)),
+        ];
+        let (admitted, errors) = ReportChannel::admit_batch(reports, &counters);
+        assert_eq!((admitted.len(), errors), (2, 1));
+        assert_eq!(counters.reported.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn bugs_cant_be_filtered() {
+        let levels = [
+            ReportLevel::Silent,
+            ReportLevel::Error,
+            ReportLevel::Warn,
+            ReportLevel::Advice,
+        ];
+        for threshold in levels {
+            assert!(ReportLevel::Bug.is_shown_at(threshold));
+        }
+        assert!(!ReportLevel::Error.is_shown_at(ReportLevel::Silent));
+        assert!(!ReportLevel::Warn.is_shown_at(ReportLevel::Error));
+        // Nor hidden by --max-reports.
+        let counters = ReportCounters::default();
+        counters.reported.store(usize::MAX, Ordering::SeqCst);
+        let reports = vec![
+            report(ReportLevel::Error, "error", None),
+            report(ReportLevel::Bug, "bug", None),
+        ];
+        assert_eq!(admitted_titles(reports, &counters), ["bug"]);
+        assert_eq!(counters.suppressed.load(Ordering::SeqCst), 1);
+    }
+}
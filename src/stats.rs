@@ -0,0 +1,109 @@
+use crate::ast::Node;
+use crate::eoutln;
+use crate::vm::Program;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "count-alloc")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()), Ordering::Relaxed);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+}
+
+#[derive(Default)]
+pub struct Stats {
+    phases: Vec<(&'static str, Duration)>,
+    ast_nodes: Option<usize>,
+    chunk_bytes: Option<usize>,
+    constants: Option<(usize, usize)>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn time<T, F: FnOnce() -> T>(&mut self, phase: &'static str, f: F) -> T {
+        let start = Instant::now();
+        let out = f();
+        self.phases.push((phase, start.elapsed()));
+        out
+    }
+
+    pub fn record_ast(&mut self, ast: &Node) {
+        self.ast_nodes = Some(ast.count());
+    }
+
+    pub fn record_program(&mut self, program: &Program) {
+        let chunks = &program.chunks;
+        self.chunk_bytes = Some(chunks.iter().map(|chunk| chunk.source.len()).sum());
+        self.constants = Some((program.constant_count(), program.constant_bytes()));
+    }
+
+    pub fn print(&self) {
+        eoutln!("Time:");
+        for (phase, duration) in &self.phases {
+            eoutln!("  {:<16}{:.3}ms", phase, duration.as_secs_f64() * 1000.0);
+        }
+        eoutln!("Memory:");
+        match peak_rss() {
+            Some(kb) => eoutln!("  {:<16}{} kB", "peak rss", kb),
+            None => eoutln!("  {:<16}unavailable", "peak rss"),
+        }
+        #[cfg(feature = "count-alloc")]
+        eoutln!(
+            "  {:<16}{} bytes",
+            "allocated",
+            counting::ALLOCATED.load(std::sync::atomic::Ordering::Relaxed)
+        );
+        if let Some(nodes) = self.ast_nodes {
+            eoutln!("  {:<16}{}", "ast nodes", nodes);
+        }
+        if let Some(bytes) = self.chunk_bytes {
+            eoutln!("  {:<16}{} bytes", "chunk size", bytes);
+        }
+        if let Some((count, bytes)) = self.constants {
+            eoutln!("  {:<16}{} ({} bytes)", "constant pool", count, bytes);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn peak_rss() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_rss() -> Option<usize> {
+    None
+}
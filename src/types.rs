@@ -1,7 +1,6 @@
 use crate::ast::Node;
-use name_variant::NamedVariant;
 
-#[derive(NamedVariant, Clone)]
+#[derive(Clone)]
 pub enum Type {
     Number,
     Float,
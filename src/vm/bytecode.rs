@@ -1,6 +1,13 @@
+use crate::ast::span::Span;
 use crate::vm::value::Value;
+use crate::{out, outln};
 use int_enum::IntEnum;
 use name_variant::NamedVariant;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 #[repr(u8)]
 #[derive(IntEnum, NamedVariant, Debug, Copy, Clone)]
@@ -12,23 +19,123 @@ pub enum OpCode {
     Mul = 5,
     Div = 6,
     Less = 7,
-    Greater = 8,
+    LessEqual = 8,
     Equal = 9,
-    And = 11,
-    Or = 12,
     Not = 10,
+    FloorDiv = 13,
+    Swap = 14,
+    Pop = 15,
+    /// Moves forward by its `u16` operand, counted from after the operand.
+    Jump = 16,
+    /// Pops a boolean, and jumps like `Jump` if it's false. Any other value
+    /// is an error, so the condition of an `if` or `while` has to be a
+    /// boolean.
+    JumpIfFalse = 17,
+    /// Moves backward by its `u16` operand, counted from after the operand.
+    Loop = 18,
+    /// Pushes a copy of the local in the stack slot given by its `u8`
+    /// operand.
+    GetLocal = 19,
+    /// Stores the top of the stack, without popping it, in the local in the
+    /// stack slot given by its `u8` operand.
+    SetLocal = 20,
+    /// Pops a value into the global named by the string constant at its
+    /// `u16` operand, declaring it or replacing its value.
+    DefineGlobal = 21,
+    /// Pushes the global named by the string constant at its `u16` operand.
+    GetGlobal = 22,
+    /// Stores the top of the stack, without popping it, in the existing
+    /// global named by the string constant at its `u16` operand.
+    SetGlobal = 23,
+    BitAnd = 24,
+    BitOr = 25,
+    BitXor = 26,
+    BitNot = 27,
+    Shl = 28,
+    /// An arithmetic shift, keeping the sign.
+    Shr = 29,
+    Negate = 30,
+    /// Pops a value, and jumps like `Jump` if it's truthy. See
+    /// [`Value::is_truthy`].
+    JumpIfTruthy = 31,
+    Dup = 32,
+    /// Moves the top of the stack under the two values below it.
+    Rot = 33,
+    /// Pops as many values as its `u16` operand into a new list, the
+    /// deepest first.
+    BuildList = 34,
+    /// Pops an index, then the value to index, and pushes the element.
+    Index = 35,
+    /// Pops as many key-value pairs as its `u16` operand into a new map,
+    /// each key below its value and the deepest pair first.
+    BuildMap = 36,
+    /// Pops the end bound, the start bound, then the value to slice, and
+    /// pushes the slice. A bound of `None` means it was left out.
+    Slice = 37,
+    /// Pushes the table of the module compiled into the chunk at its `u16`
+    /// operand, running the module first if it hasn't been yet.
+    Import = 38,
+    /// Pushes `None`, which needs no slot in the constant pool.
+    None = 39,
+    /// Pops the end, then the start, and pushes the range between them. Its
+    /// `u8` operand is 1 if the range is inclusive, and 0 otherwise.
+    BuildRange = 40,
+    /// Pops a range, and pushes its first integer and the one after its
+    /// last.
+    RangeBounds = 41,
+    /// Pops a container, then a value, and pushes whether the value is in
+    /// the container.
+    Contains = 42,
+    /// Pops a list, and pushes its elements for a destructuring `let`. Its
+    /// `u8` operand is how many there have to be.
+    Unpack = 43,
+    /// Like `Unpack`, but the list can be longer than the `u8` operand, and
+    /// the elements after those are pushed as one more list.
+    UnpackRest = 44,
+    /// Pops a value, and jumps like `Jump` if it's falsy.
+    JumpIfFalsy = 45,
+}
+
+impl OpCode {
+    /// The opcode's name, as the disassembler and `--profile` show it.
+    pub fn name(&self) -> &'static str {
+        self.variant_name()
+    }
 }
 
 pub struct Chunk {
+    pub name: String,
     pub source: Vec<u8>,
     constants: Vec<Value>,
+    /// Indices into `constants`, bucketed by the hash of the value, so
+    /// deduplicating a constant doesn't scan the whole pool.
+    const_index: HashMap<u64, Vec<u16>>,
+    /// The offset and span of the first instruction of each statement, in
+    /// order of offset.
+    statements: Vec<(usize, Span)>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
+    /// `Const` takes a `u16` operand, so a pool can't address more than this.
+    pub const MAX_CONSTANTS: usize = u16::MAX as usize + 1;
+
     pub fn new() -> Self {
+        Self::named("<script>")
+    }
+
+    pub fn named<T: Display>(name: T) -> Self {
         Self {
+            name: name.to_string(),
             source: Vec::new(),
             constants: Vec::new(),
+            const_index: HashMap::new(),
+            statements: Vec::new(),
         }
     }
 
@@ -70,69 +177,367 @@ impl Chunk {
         self.write_u32(value);
     }
 
-    pub fn write_const(&mut self, value: Value) {
-        if let Some(idx) = self.constants.iter().position(|v| *v == value) {
-            self.write_op_with_u16(OpCode::Const, idx as u16);
-        } else {
-            let idx = self.constants.len();
-            self.constants.push(value);
-            self.write_op_with_u16(OpCode::Const, idx as u16);
+    /// Writes `op` with a placeholder distance, returning the offset of the
+    /// operand so [`Chunk::patch_jump`] can fill it in once the target is
+    /// known.
+    pub fn write_jump(&mut self, op: OpCode) -> usize {
+        self.write_op_with_u16(op, u16::MAX);
+        self.source.len() - 2
+    }
+
+    /// Points the jump whose operand is at `operand` at the next
+    /// instruction to be written. Returns `None` if that's too far away for
+    /// the operand to hold.
+    pub fn patch_jump(&mut self, operand: usize) -> Option<()> {
+        let distance = u16::try_from(self.source.len() - operand - 2).ok()?;
+        self.source[operand..operand + 2].copy_from_slice(&distance.to_be_bytes());
+        Some(())
+    }
+
+    /// Writes a `Loop` back to the instruction at `target`. Returns `None`
+    /// if that's too far away for the operand to hold.
+    pub fn write_loop(&mut self, target: usize) -> Option<()> {
+        // The distance is counted from after the operand.
+        let distance = u16::try_from(self.source.len() + 3 - target).ok()?;
+        self.write_op_with_u16(OpCode::Loop, distance);
+        Some(())
+    }
+
+    /// Adds `value` to the constant pool, reusing an equal entry if one
+    /// exists, and returns its index. Returns `None` if the pool is already
+    /// holding [`Chunk::MAX_CONSTANTS`] other values.
+    pub fn add_const(&mut self, value: Value) -> Option<u16> {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let bucket = self.const_index.entry(hasher.finish()).or_default();
+        if let Some(idx) = bucket
+            .iter()
+            .find(|idx| self.constants[**idx as usize] == value)
+        {
+            return Some(*idx);
         }
+        let idx = u16::try_from(self.constants.len()).ok()?;
+        bucket.push(idx);
+        self.constants.push(value);
+        Some(idx)
     }
 
-    pub fn read_u8(&mut self, offset: &mut usize) -> u8 {
-        let res = self.source[*offset];
-        *offset += 1;
-        res
+    /// Writes a `Const` instruction loading `value`, returning `None`
+    /// without writing anything if the constant pool is full.
+    pub fn write_const(&mut self, value: Value) -> Option<u16> {
+        let idx = self.add_const(value)?;
+        self.write_op_with_u16(OpCode::Const, idx);
+        Some(idx)
     }
 
-    pub fn read_u16(&mut self, offset: &mut usize) -> u16 {
-        let res: u16 = (self.read_u8(offset) as u16) << 8 | self.read_u8(offset) as u16;
-        res
+    /// Records that the next instruction written starts the statement at
+    /// `span`.
+    pub fn mark_statement(&mut self, span: Span) {
+        self.statements.push((self.source.len(), span));
     }
 
-    pub fn read_u32(&mut self, offset: &mut usize) -> u32 {
-        let res: u32 = (self.read_u16(offset) as u32) << 16 | self.read_u16(offset) as u32;
-        res
+    /// The span of the statement the instruction at `offset` belongs to,
+    /// taken to be the last one to start at or before it.
+    pub fn statement_containing(&self, offset: usize) -> Option<Span> {
+        let idx = self
+            .statements
+            .partition_point(|(start, _)| *start <= offset);
+        idx.checked_sub(1).map(|idx| self.statements[idx].1)
+    }
+
+    /// The span of the statement starting at `offset`, if one does.
+    pub fn statement_at(&self, offset: usize) -> Option<Span> {
+        self.statements
+            .binary_search_by_key(&offset, |(start, _)| *start)
+            .ok()
+            .map(|idx| self.statements[idx].1)
+    }
+
+    /// Builds a chunk from operand-less instructions. Use the `with_*`
+    /// methods to append instructions that carry operands.
+    #[cfg(test)]
+    pub fn from_ops(ops: &[OpCode]) -> Self {
+        ops.iter().fold(Self::new(), |chunk, op| chunk.with_op(*op))
     }
 
-    pub fn read_op(&mut self, offset: &mut usize) -> OpCode {
-        let op = self.read_u8(offset).try_into().unwrap();
-        op
+    /// Appends `op` without any operand. The caller is responsible for
+    /// following it with the operands that `op` expects.
+    #[cfg(test)]
+    pub fn with_op(mut self, op: OpCode) -> Self {
+        self.write_op(op);
+        self
     }
 
-    pub fn read_const(&mut self, offset: &mut usize) -> Value {
-        let idx = self.read_u16(offset);
-        self.constants[idx as usize].clone()
+    #[cfg(test)]
+    pub fn with_u8(mut self, value: u8) -> Self {
+        self.write_u8(value);
+        self
     }
 
-    pub fn disassemble_op(&mut self, op: OpCode, offset: &mut usize) {
-        print!("{:03} | {:#04x} {}", offset, op as usize, op.variant_name());
+    #[cfg(test)]
+    pub fn with_u16(mut self, value: u16) -> Self {
+        self.write_u16(value);
+        self
+    }
+
+    /// Appends a complete `Const` instruction loading `value`.
+    #[cfg(test)]
+    pub fn with_const(mut self, value: Value) -> Self {
+        self.write_const(value).expect("Constant pool is full");
+        self
+    }
+
+    pub fn constant_count(&self) -> usize {
+        self.constants.len()
+    }
+
+    /// The size of the pool itself. String contents live in the
+    /// [`Program`] string table and are counted there.
+    pub fn constant_bytes(&self) -> usize {
+        self.constants.len() * std::mem::size_of::<Value>()
+    }
+
+    pub fn constant(&self, idx: u16) -> &Value {
+        &self.constants[idx as usize]
+    }
+
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(self)
+    }
+
+    /// Prints the instruction at the cursor and moves past it.
+    pub fn disassemble_instruction(cursor: &mut Cursor<'_>) {
+        let offset = cursor.offset();
+        let op = cursor.read_op();
+        out!("{:03} | {:#04x} {}", offset, op as usize, op.name());
         match op {
-            OpCode::Const => {
-                let idx = self.read_u16(offset);
-                let val = &self.constants[idx as usize];
-                print!(" | {:04x} = {:?}", idx, val);
+            OpCode::Const | OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal => {
+                let idx = cursor.read_const_index();
+                out!(" | {:04x} = {:?}", idx, cursor.chunk().constant(idx));
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::JumpIfFalsy | OpCode::JumpIfTruthy => {
+                let distance = cursor.read_u16();
+                out!(
+                    " | {:04x} -> {:03}",
+                    distance,
+                    cursor.offset() + distance as usize
+                );
+            }
+            OpCode::BuildList | OpCode::BuildMap | OpCode::Import => {
+                out!(" | {}", cursor.read_u16());
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::BuildRange
+            | OpCode::Unpack
+            | OpCode::UnpackRest => {
+                out!(" | {:02x}", cursor.read_u8());
+            }
+            OpCode::Loop => {
+                let distance = cursor.read_u16();
+                out!(
+                    " | {:04x} -> {:03}",
+                    distance,
+                    cursor.offset() - distance as usize
+                );
             }
             _ => (),
         }
-        println!();
+        outln!();
     }
 
-    pub fn disassemble(&mut self) {
-        let mut offset = 0;
+    pub fn disassemble(&self) {
+        outln!("== {} ==", self.name);
+        let mut cursor = self.cursor();
         let mut instructions = 0usize;
 
-        while offset < self.source.len() {
+        while !cursor.at_end() {
             instructions += 1;
-            let op = self.read_op(&mut offset);
-            self.disassemble_op(op, &mut offset);
+            Self::disassemble_instruction(&mut cursor);
         }
 
-        println!(
+        outln!(
             "{} instructions and {} bytes",
             instructions,
             self.source.len()
         );
     }
 }
+
+/// Reads a chunk's instructions and their operands in order. This is the
+/// only place bytecode is decoded, so the VM and the disassembler can't
+/// disagree about an operand's width.
+///
+/// Operands stay inline in the byte stream after their opcode, rather than
+/// in a separate aligned array. No instruction has more than a two byte
+/// operand yet, so an instruction's bytes are always read together.
+///
+/// Every read panics if the chunk ends partway through it, which only a
+/// malformed chunk can cause.
+#[derive(Copy, Clone)]
+pub struct Cursor<'c> {
+    chunk: &'c Chunk,
+    offset: usize,
+}
+
+impl<'c> Cursor<'c> {
+    pub fn new(chunk: &'c Chunk) -> Self {
+        Self { chunk, offset: 0 }
+    }
+
+    pub fn chunk(&self) -> &'c Chunk {
+        self.chunk
+    }
+
+    /// The offset of the next byte to be read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.offset >= self.chunk.source.len()
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        let res = self.chunk.source[self.offset];
+        self.offset += 1;
+        res
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        (self.read_u8() as u16) << 8 | self.read_u8() as u16
+    }
+
+    pub fn read_op(&mut self) -> OpCode {
+        let byte = self.read_u8();
+        byte.try_into()
+            .unwrap_or_else(|_| panic!("Invalid opcode {byte:#04x}"))
+    }
+
+    /// Moves forward `distance` bytes from the current offset.
+    pub fn jump(&mut self, distance: u16) {
+        self.offset += distance as usize;
+    }
+
+    /// Moves backward `distance` bytes from the current offset.
+    pub fn jump_back(&mut self, distance: u16) {
+        self.offset -= distance as usize;
+    }
+
+    pub fn read_const_index(&mut self) -> u16 {
+        self.read_u16()
+    }
+
+    pub fn read_const(&mut self) -> &'c Value {
+        let idx = self.read_const_index();
+        self.chunk.constant(idx)
+    }
+}
+
+/// Every chunk produced by compiling a source file: the top level script
+/// at [`Program::MAIN`], followed by one chunk per imported module or
+/// function body.
+#[derive(Default)]
+pub struct Program {
+    pub chunks: Vec<Chunk>,
+    /// Every string constant in the program, shared by the chunks that
+    /// load it.
+    strings: HashSet<Rc<str>>,
+}
+
+impl Program {
+    pub const MAIN: usize = 0;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the program's shared copy of `string`, adding it to the
+    /// string table if this is its first use.
+    pub fn intern(&mut self, string: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(string) {
+            return interned.clone();
+        }
+        let interned: Rc<str> = string.into();
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    pub fn constant_count(&self) -> usize {
+        self.chunks.iter().map(Chunk::constant_count).sum()
+    }
+
+    /// The size of every chunk's constant pool plus the string table.
+    pub fn constant_bytes(&self) -> usize {
+        self.chunks.iter().map(Chunk::constant_bytes).sum::<usize>()
+            + self.strings.iter().map(|s| s.len()).sum::<usize>()
+    }
+
+    pub fn main(&self) -> &Chunk {
+        &self.chunks[Self::MAIN]
+    }
+
+    pub fn disassemble(&self) {
+        for chunk in &self.chunks {
+            chunk.disassemble();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelToken;
+    use crate::pipeline::compile_source;
+    use crate::pipeline::testing::{eval, filename};
+    use crate::vm::VmOptions;
+
+    #[test]
+    fn strings_are_stored_once() {
+        let mut source = "let x = \"\"\n".to_string();
+        for i in 0..10_000 {
+            source += &format!("x = \"string {}\"\n", i % 100);
+        }
+        source += "return x";
+        let Ok((program, _)) = compile_source(
+            filename(),
+            source.clone(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) else {
+            panic!("Failed to compile");
+        };
+        // The literals, the empty string and the name `x`.
+        assert_eq!(program.strings.len(), 102);
+        assert_eq!(program.constant_count(), 102);
+        for value in &program.main().constants {
+            let Value::String(string) = value else {
+                continue;
+            };
+            assert!(Rc::ptr_eq(string, program.strings.get(string).unwrap()));
+        }
+        assert_eq!(eval(&source), Value::String("string 99".into()));
+    }
+
+    #[test]
+    fn reads_operands_big_endian() {
+        let chunk = Chunk::new().with_u8(0x12).with_u16(0x3456);
+        let mut cursor = chunk.cursor();
+        assert_eq!(cursor.read_u8(), 0x12);
+        assert_eq!(cursor.read_u16(), 0x3456);
+        assert!(cursor.at_end());
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_u8_past_the_end() {
+        Chunk::new().cursor().read_u8();
+    }
+
+    #[test]
+    #[should_panic]
+    fn read_u16_past_the_end() {
+        // Only one of its two bytes is there.
+        Chunk::new().with_u8(1).cursor().read_u16();
+    }
+}
@@ -1,103 +1,1843 @@
-use crate::ast::{Node, NodeKind, Operator};
-use crate::vm::bytecode::{Chunk, OpCode};
-use crate::vm::Value;
+use crate::ast::parser::Parser;
+use crate::ast::span::Span;
+use crate::ast::symbol::Symbol;
+use crate::ast::{Node, NodeKind, Operator, Pattern, PatternKind};
+use crate::cancel::{CancelToken, Cancelled};
+use crate::report::{ReportKind, ReportLevel, ReportSink, SpanToLabel};
+use crate::vm::bytecode::{Chunk, OpCode, Program};
+use crate::vm::{Value, VmOptions};
+use name_variant::NamedVariant;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use CompilerAdvice::*;
+use CompilerError::*;
+use CompilerWarning::*;
+
+#[derive(NamedVariant)]
+enum CompilerError {
+    /// The name of the chunk whose constant pool is full.
+    TooManyConstants(String),
+    /// A jump over more bytecode than its operand can hold.
+    JumpTooFar,
+    TooManyLocals,
+    UndefinedVariable(Symbol),
+    TooManyItems,
+    TooManyChunks,
+    BreakOutsideLoop,
+    /// A `break` with a value, in a loop that doesn't produce one.
+    BreakWithValue,
+    /// A range with literal ends that can't make one.
+    InvalidRange,
+    /// An import of a module from outside the import root.
+    ImportOutsideRoot,
+}
+
+impl ReportKind for CompilerError {
+    fn title(&self) -> String {
+        match self {
+            TooManyConstants(chunk) => format!("{} in {}", self.variant_name(), chunk),
+            JumpTooFar | TooManyLocals | TooManyItems | TooManyChunks | BreakOutsideLoop
+            | BreakWithValue | InvalidRange | ImportOutsideRoot => self.variant_name().to_string(),
+            UndefinedVariable(name) => format!("{} `{}`", self.variant_name(), name),
+        }
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Error
+    }
+}
+
+#[derive(NamedVariant)]
+enum CompilerWarning {
+    EmptyMatch,
+    UnreachableArm,
+}
+
+impl ReportKind for CompilerWarning {
+    fn title(&self) -> String {
+        self.variant_name().to_string()
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Warn
+    }
+}
+
+#[derive(NamedVariant)]
+enum CompilerAdvice {
+    ImportCycle,
+    /// The value of a branch of an `if` that's used as a statement.
+    DiscardedValue,
+}
+
+impl ReportKind for CompilerAdvice {
+    fn title(&self) -> String {
+        self.variant_name().to_string()
+    }
+
+    fn level(&self) -> ReportLevel {
+        ReportLevel::Advice
+    }
+}
+
+/// A local variable, living in a stack slot above those of the locals
+/// before it in [`Compiler::locals`].
+struct Local {
+    /// `None` for slots the compiler keeps for itself, which code can't
+    /// refer to.
+    name: Option<Symbol>,
+    /// The scope depth it was declared at.
+    depth: usize,
+    slot: u8,
+    /// The values held below it by the expression it was declared in.
+    held: usize,
+}
+
+/// A loop being compiled, for the `break`s inside it.
+struct LoopContext {
+    /// The operands of the jumps out of the loop, to patch at its end.
+    breaks: Vec<usize>,
+    /// How many values are on the stack where the breaks land. Any locals
+    /// declared since, and operands held by a `do` block the `break` is in,
+    /// are popped before jumping.
+    height: usize,
+    /// Whether the loop evaluates to the value given to `break`.
+    yields_value: bool,
+}
+
+/// Where a name resolved to.
+#[derive(Copy, Clone)]
+enum Variable {
+    Local(u8),
+    Global(Symbol),
+}
 
 pub struct Compiler {
-    pub chunk: Chunk,
+    pub program: Program,
+    /// Indices into `program.chunks` of the chunks being compiled, with the
+    /// innermost function body last.
+    chunk_stack: Vec<usize>,
+    locals: Vec<Local>,
+    /// How many values the expression being compiled holds on the stack
+    /// above the last local, like the left operand of `+` while the right
+    /// one is compiled. A local declared partway through an expression
+    /// goes above them.
+    held: usize,
+    /// The loops being compiled, innermost last.
+    loops: Vec<LoopContext>,
+    scope_depth: usize,
+    /// The globals declared so far, so reading any other name outside a
+    /// local scope can be reported at compile time.
+    globals: HashSet<Symbol>,
+    /// The chunk each module imported so far was compiled into, by the
+    /// canonical path of its file.
+    modules: HashMap<PathBuf, u16>,
+    /// The canonical path of each file being compiled, outermost first,
+    /// with its path as written and the import that started compiling it.
+    loading: Vec<(PathBuf, &'static str, Option<Span>)>,
+    /// The canonical path of the directory modules have to be imported
+    /// from, if there's a limit.
+    import_root: Option<PathBuf>,
+    reporter: Rc<dyn ReportSink>,
+    cancel: CancelToken,
 }
 
 impl Compiler {
-    pub fn new() -> Self {
+    pub fn new<R: ReportSink + 'static>(reporter: R) -> Self {
         Self {
-            chunk: Chunk::new(),
+            program: Program::new(),
+            chunk_stack: Vec::new(),
+            locals: Vec::new(),
+            held: 0,
+            loops: Vec::new(),
+            scope_depth: 0,
+            globals: HashSet::new(),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            import_root: None,
+            reporter: Rc::new(reporter),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Takes the options that apply to compiling, which is only the import
+    /// root.
+    pub fn with_options(mut self, options: &VmOptions) -> Self {
+        self.import_root = options.import_root.as_deref().map(Self::canonical_path);
+        self
+    }
+
+    /// Declares globals that already exist, like those a [`VM`] kept from
+    /// the programs it ran before, so the program compiled next can use
+    /// them.
+    ///
+    /// [`VM`]: crate::vm::VM
+    pub fn with_globals(mut self, names: impl IntoIterator<Item = Symbol>) -> Self {
+        self.globals.extend(names);
+        self
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        let idx = *self.chunk_stack.last().expect("No chunk to compile into");
+        &mut self.program.chunks[idx]
+    }
+
+    /// Writes a `Const` instruction loading `value`, reporting an error
+    /// at `span` if the current chunk can't hold another constant.
+    fn write_const(&mut self, value: Value, span: Span) {
+        if self.chunk().write_const(value).is_none() {
+            self.report_too_many_constants(span);
+        }
+    }
+
+    /// Writes `op` with the index of a string constant holding `name`, as
+    /// the global instructions take.
+    fn write_global_op(&mut self, op: OpCode, name: Symbol, span: Span) {
+        let name = Value::String(self.program.intern(name.as_str()));
+        match self.chunk().add_const(name) {
+            Some(idx) => self.chunk().write_op_with_u16(op, idx),
+            None => self.report_too_many_constants(span),
+        }
+    }
+
+    fn report_too_many_constants(&mut self, span: Span) {
+        let name = self.chunk().name.clone();
+        self.reporter.report(
+            TooManyConstants(name)
+                .make_labeled(span.label())
+                .with_note(format!(
+                    "A chunk can hold at most {} distinct constants",
+                    Chunk::MAX_CONSTANTS
+                ))
+                .finish()
+                .into(),
+        );
+    }
+
+    /// Points the jump with its operand at `operand` at the next
+    /// instruction, reporting an error at `span` if it's too far away.
+    fn patch_jump(&mut self, operand: usize, span: Span) {
+        if self.chunk().patch_jump(operand).is_none() {
+            self.report_jump_too_far(span);
+        }
+    }
+
+    fn report_jump_too_far(&mut self, span: Span) {
+        self.reporter.report(
+            JumpTooFar
+                .make_labeled(span.label())
+                .with_note(format!(
+                    "A jump can skip at most {} bytes of bytecode",
+                    u16::MAX
+                ))
+                .finish()
+                .into(),
+        );
+    }
+
+    /// `GetLocal` and `SetLocal` take a `u8` operand, so a chunk can't
+    /// address more locals than this.
+    const MAX_LOCALS: usize = u8::MAX as usize + 1;
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    /// Leaves the innermost scope, popping the locals declared in it.
+    fn end_scope(&mut self) {
+        self.leave_scope(false);
+    }
+
+    /// Leaves the innermost scope, popping its locals from under the value
+    /// on top of the stack, which the scope evaluated to.
+    fn end_scope_with_value(&mut self) {
+        self.leave_scope(true);
+    }
+
+    fn leave_scope(&mut self, keep_value: bool) {
+        self.scope_depth -= 1;
+        while self
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > self.scope_depth)
+        {
+            self.held = self.locals.pop().unwrap().held;
+            if keep_value {
+                self.chunk().write_op(OpCode::Swap);
+            }
+            self.chunk().write_op(OpCode::Pop);
         }
     }
 
+    /// How many values the chunk has on the stack at this point: its locals,
+    /// and the operands held above them.
+    fn stack_height(&self) -> usize {
+        self.locals
+            .last()
+            .map_or(0, |local| local.slot as usize + 1)
+            + self.held
+    }
+
+    /// Declares a local in the current scope for the value on top of the
+    /// stack, returning its slot. Reports an error at `span` and returns
+    /// `None` if there are no slots left.
+    fn add_local(&mut self, name: Option<Symbol>, span: Span) -> Option<u8> {
+        let slot = self.stack_height();
+        if slot >= Self::MAX_LOCALS {
+            self.reporter.report(
+                TooManyLocals
+                    .make_labeled(span.label())
+                    .with_note(format!(
+                        "A chunk can hold at most {} locals at once",
+                        Self::MAX_LOCALS
+                    ))
+                    .finish()
+                    .into(),
+            );
+            return None;
+        }
+        self.locals.push(Local {
+            name,
+            depth: self.scope_depth,
+            slot: slot as u8,
+            held: std::mem::take(&mut self.held),
+        });
+        Some(slot as u8)
+    }
+
+    /// The slot of the innermost local named `name`, if there is one.
+    fn resolve_local(&self, name: Symbol) -> Option<u8> {
+        self.locals
+            .iter()
+            .rfind(|local| local.name == Some(name))
+            .map(|local| local.slot)
+    }
+
+    /// Resolves `name` to the innermost local, or else a global declared so
+    /// far.
+    fn resolve(&self, name: Symbol) -> Option<Variable> {
+        match self.resolve_local(name) {
+            Some(slot) => Some(Variable::Local(slot)),
+            None if self.globals.contains(&name) => Some(Variable::Global(name)),
+            None => None,
+        }
+    }
+
+    /// Declares `name` for the value on top of the stack, as a global at
+    /// the top level of a file and as a local anywhere else.
+    fn declare(&mut self, name: Symbol, span: Span) {
+        if self.scope_depth == 0 {
+            self.globals.insert(name);
+            self.write_global_op(OpCode::DefineGlobal, name, span);
+        } else {
+            self.add_local(Some(name), span);
+        }
+    }
+
+    fn write_get(&mut self, var: Variable, span: Span) {
+        match var {
+            Variable::Local(slot) => self.chunk().write_op_with_u8(OpCode::GetLocal, slot),
+            Variable::Global(name) => self.write_global_op(OpCode::GetGlobal, name, span),
+        }
+    }
+
+    fn write_set(&mut self, var: Variable, span: Span) {
+        match var {
+            Variable::Local(slot) => self.chunk().write_op_with_u8(OpCode::SetLocal, slot),
+            Variable::Global(name) => self.write_global_op(OpCode::SetGlobal, name, span),
+        }
+    }
+
+    /// Starts compiling into a new chunk, returning its index in the program.
+    fn begin_chunk(&mut self, chunk: Chunk) -> usize {
+        let idx = self.program.chunks.len();
+        self.program.chunks.push(chunk);
+        self.chunk_stack.push(idx);
+        idx
+    }
+
+    fn end_chunk(&mut self) {
+        self.chunk_stack.pop();
+    }
+
     pub fn compile_program(&mut self, program: &Node) {
+        let path = program.span.filename;
+        let main = self.begin_chunk(Chunk::named(path));
+        debug_assert_eq!(main, Program::MAIN);
+        // A module importing the main file gets its table, like any other
+        // cycle, rather than a second copy of it.
+        let canonical = Self::canonical_path(path);
+        self.modules.insert(canonical.clone(), Program::MAIN as u16);
+        self.loading.push((canonical, path, None));
+        self.compile_script(program);
+        self.loading.pop();
+        self.end_chunk();
+        if crate::args::ARGS.show_bytecode() {
+            self.program.disassemble();
+        }
+    }
+
+    /// Compiles the top level statements of a file into the current chunk.
+    fn compile_script(&mut self, program: &Node) {
         let NodeKind::Block(stmts) = &program.kind else {
             unreachable!()
         };
         for stmt in stmts {
-            self.compile(stmt);
+            self.compile_statement(stmt);
+            if self.cancel.is_cancelled() {
+                self.reporter.report(
+                    Cancelled
+                        .make_labeled(stmt.span.labeled("Stopped while compiling this statement"))
+                        .finish()
+                        .into(),
+                );
+                break;
+            }
+        }
+    }
+
+    /// Compiles the module at `path` into a chunk of its own, with its own
+    /// globals, and returns the chunk's index. A module that has already
+    /// been compiled isn't compiled again.
+    fn compile_module(&mut self, path: &'static str, span: Span) -> Option<u16> {
+        let canonical = Self::canonical_path(path);
+        if let Some(root) = &self.import_root {
+            if !canonical.starts_with(root) {
+                self.reporter.report(
+                    ImportOutsideRoot
+                        .make_labeled(span.labeled("Imported here"))
+                        .with_note(format!(
+                            "{} isn't inside {}",
+                            canonical.display(),
+                            root.display()
+                        ))
+                        .finish()
+                        .into(),
+                );
+                return None;
+            }
         }
-        if crate::ARGS.show_bytecode() {
-            self.chunk.disassemble();
+        if let Some(&chunk) = self.modules.get(&canonical) {
+            if let Some(start) = self.loading.iter().position(|(p, _, _)| *p == canonical) {
+                self.report_import_cycle(start, span);
+            }
+            return Some(chunk);
+        }
+        let Ok(chunk) = u16::try_from(self.program.chunks.len()) else {
+            self.reporter.report(
+                TooManyChunks
+                    .make_labeled(span.label())
+                    .with_note(format!("A program can hold at most {} chunks", u16::MAX))
+                    .finish()
+                    .into(),
+            );
+            return None;
+        };
+        let ast = match Parser::new(path, self.reporter.clone()) {
+            Ok(parser) => parser.with_cancel(self.cancel.clone()).parse(),
+            Err(report) => {
+                self.reporter.report(report.finish().into());
+                return None;
+            }
+        };
+        // Cached before compiling, so an import cycle back to this module
+        // gets this chunk rather than recursing forever.
+        self.modules.insert(canonical.clone(), chunk);
+        self.loading.push((canonical, path, Some(span)));
+        let locals = std::mem::take(&mut self.locals);
+        let held = std::mem::take(&mut self.held);
+        let loops = std::mem::take(&mut self.loops);
+        let scope_depth = std::mem::take(&mut self.scope_depth);
+        let globals = std::mem::take(&mut self.globals);
+        self.begin_chunk(Chunk::named(path));
+        self.compile_script(&ast);
+        self.end_chunk();
+        self.loading.pop();
+        self.locals = locals;
+        self.held = held;
+        self.loops = loops;
+        self.scope_depth = scope_depth;
+        self.globals = globals;
+        Some(chunk)
+    }
+
+    fn canonical_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let path = path.as_ref();
+        std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Advises that the import at `span` leads back to `loading[start]`,
+    /// which is still being compiled. At runtime the import gets that
+    /// module's table as it is so far, which may be missing names it
+    /// defines later.
+    fn report_import_cycle(&mut self, start: usize, span: Span) {
+        let cycle = &self.loading[start..];
+        let mut report = ImportCycle.make_labeled(span.labeled(format!(
+            "Imports {} before it has finished loading",
+            cycle[0].1
+        )));
+        for (_, path, import) in &cycle[1..] {
+            if let Some(import) = import {
+                report.push_label(import.labeled(format!("Imports {path}")));
+            }
         }
+        let mut names: Vec<_> = cycle.iter().map(|(_, path, _)| *path).collect();
+        names.push(cycle[0].1);
+        self.reporter.report(
+            report
+                .with_note(format!("The cycle is {}", names.join(" -> ")))
+                .with_help(format!(
+                    "Until it finishes loading, {} only has the names defined above its imports",
+                    cycle[0].1
+                ))
+                .finish()
+                .into(),
+        );
+    }
+
+    /// How a comparison operator is lowered, as the opcode to apply, whether
+    /// a `Swap` precedes it, and whether a `Not` follows it.
+    ///
+    /// Operands are always evaluated left to right. `>` and `>=` swap them
+    /// and reuse `Less`/`LessEqual`, so the runtime compares `rhs` against
+    /// `lhs`: `a > b` runs as `b < a` and `a >= b` as `b <= a`. Every other
+    /// comparison sees its operands as `(lhs, rhs)`. Nothing is lowered to a
+    /// negated ordering, so a comparison involving NaN is never inverted.
+    fn comparison_lowering(op: &Operator) -> Option<(OpCode, bool, bool)> {
+        Some(match op {
+            Operator::LessThan => (OpCode::Less, false, false),
+            Operator::LessThanEquals => (OpCode::LessEqual, false, false),
+            Operator::GreaterThan => (OpCode::Less, true, false),
+            Operator::GreaterThanEquals => (OpCode::LessEqual, true, false),
+            Operator::Equals => (OpCode::Equal, false, false),
+            Operator::BangEquals => (OpCode::Equal, false, true),
+            Operator::In => (OpCode::Contains, false, false),
+            Operator::NotIn => (OpCode::Contains, false, true),
+            _ => return None,
+        })
     }
 
     pub fn handle_binary_op(&mut self, op: &Operator) {
-        self.chunk.write_op(match op {
+        if let Some((opcode, swap, negate)) = Self::comparison_lowering(op) {
+            if swap {
+                self.chunk().write_op(OpCode::Swap);
+            }
+            self.chunk().write_op(opcode);
+            if negate {
+                self.chunk().write_op(OpCode::Not);
+            }
+            return;
+        }
+        self.chunk().write_op(match op {
             Operator::Plus => OpCode::Add,
             Operator::Minus => OpCode::Sub,
             Operator::Star => OpCode::Mul,
             Operator::Slash => OpCode::Div,
-            Operator::Or => OpCode::Or,
-            Operator::And => OpCode::And,
-            Operator::GreaterThan => OpCode::Greater,
-            Operator::LessThan => OpCode::Less,
-            Operator::GreaterThanEquals => OpCode::Less, // Swap direction and invert result
-            Operator::LessThanEquals => OpCode::Greater,
-            Operator::Equals => OpCode::Equal,
-            Operator::BangEquals => OpCode::Equal,
+            Operator::FloorDiv => OpCode::FloorDiv,
+            Operator::BitAnd => OpCode::BitAnd,
+            Operator::BitOr => OpCode::BitOr,
+            Operator::BitXor => OpCode::BitXor,
+            Operator::Shl => OpCode::Shl,
+            Operator::Shr => OpCode::Shr,
             _ => unreachable!(),
         });
-        match op {
-            Operator::GreaterThanEquals | Operator::LessThanEquals | Operator::BangEquals => {
-                self.chunk.write_op(OpCode::Not)
+    }
+
+    /// Compiles `lhs and rhs` or `lhs or rhs`, which evaluate to the
+    /// operand that decides them rather than to a boolean. `lhs` decides
+    /// `and` when it's falsy and `or` when it's truthy, and then `rhs` isn't
+    /// evaluated. Otherwise the result is `rhs`, so `None or 5` is 5 and
+    /// `"a" or "b"` is `"a"`.
+    fn compile_logical(&mut self, op: &Operator, lhs: &Node, rhs: &Node) {
+        let jump = match op {
+            Operator::And => OpCode::JumpIfFalsy,
+            Operator::Or => OpCode::JumpIfTruthy,
+            _ => unreachable!(),
+        };
+        self.compile(lhs);
+        // The jump pops the copy, leaving `lhs` as the result.
+        self.chunk().write_op(OpCode::Dup);
+        let lhs_decides = self.chunk().write_jump(jump);
+        self.chunk().write_op(OpCode::Pop);
+        self.compile(rhs);
+        self.patch_jump(lhs_decides, rhs.span);
+    }
+
+    /// Compiles `lhs ?? rhs`, which evaluates `lhs` once, and `rhs` only if
+    /// `lhs` is `None`.
+    fn compile_coalesce(&mut self, lhs: &Node, rhs: &Node) {
+        self.compile(lhs);
+        self.chunk().write_op(OpCode::Dup);
+        self.chunk().write_op(OpCode::None);
+        self.chunk().write_op(OpCode::Equal);
+        let keep_lhs = self.chunk().write_jump(OpCode::JumpIfFalse);
+        self.chunk().write_op(OpCode::Pop);
+        self.compile(rhs);
+        self.patch_jump(keep_lhs, rhs.span);
+    }
+
+    /// Compiles a chain like `a < b <= c` as `a < b and b <= c`, evaluating
+    /// `b` once and stopping at the first comparison that's false.
+    ///
+    /// Only the orderings chain. `==` and `!=` deliberately don't, so
+    /// `a == b == c` compares the boolean `a == b` with `c`, and a
+    /// parenthesized comparison is never part of a chain.
+    fn compile_comparison(&mut self, first: &Node, rest: &[(Operator, Node)]) {
+        let Some(((last_op, last), middle)) = rest.split_last() else {
+            unreachable!("A chain has at least two comparisons")
+        };
+        self.compile(first);
+        let mut failed = Vec::new();
+        for (op, node) in middle {
+            self.compile_above(1, node);
+            // Keep a copy of this operand under the two being compared, for
+            // the next comparison to use.
+            self.chunk().write_op(OpCode::Dup);
+            self.chunk().write_op(OpCode::Rot);
+            self.handle_binary_op(op);
+            failed.push((self.chunk().write_jump(OpCode::JumpIfFalse), node.span));
+        }
+        self.compile_above(1, last);
+        self.handle_binary_op(last_op);
+        let end = self.chunk().write_jump(OpCode::Jump);
+        for (jump, span) in failed {
+            self.patch_jump(jump, span);
+        }
+        // A comparison failed before the last, leaving its right operand.
+        self.chunk().write_op(OpCode::Pop);
+        self.write_const(Value::Boolean(false), last.span);
+        self.patch_jump(end, last.span);
+    }
+
+    /// Reports a range whose ends are literals that would fail to make one
+    /// at runtime.
+    fn check_range(&mut self, start: &Node, end: &Node) {
+        fn integer(node: &Node) -> Option<i64> {
+            match &node.kind {
+                NodeKind::IntegerLiteral(value) => Some(*value),
+                NodeKind::UnaryOperation(Operator::Minus, node) => match node.kind {
+                    NodeKind::IntegerLiteral(value) => value.checked_neg(),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        for bound in [start, end] {
+            let kind = match bound.kind {
+                NodeKind::FloatLiteral(_) => "a Float",
+                NodeKind::StringLiteral(_) => "a String",
+                NodeKind::BooleanLiteral(_) => "a Boolean",
+                NodeKind::NoneLiteral => "None",
+                _ => continue,
+            };
+            self.reporter.report(
+                InvalidRange
+                    .make_labeled(bound.span.labeled(format!("This is {kind}")))
+                    .with_note("Ranges only count over integers")
+                    .finish()
+                    .into(),
+            );
+        }
+        if let (Some(first), Some(last)) = (integer(start), integer(end)) {
+            if first > last {
+                self.reporter.report(
+                    InvalidRange
+                        .make_labeled(start.span.extend(end.span).labeled("Counts down"))
+                        .with_note("Ranges can only count up, so the start can't be after the end")
+                        .finish()
+                        .into(),
+                );
+            }
+        }
+    }
+
+    /// Compiles `node` while `held` values pushed for the expression around
+    /// it wait on the stack.
+    fn compile_above(&mut self, held: usize, node: &Node) {
+        self.held += held;
+        self.compile(node);
+        self.held -= held;
+    }
+
+    /// Compiles a `match`, keeping the value matched in a hidden local that
+    /// each arm's pattern is tested against in turn. Evaluates to `None` if
+    /// no arm matches.
+    fn compile_match(&mut self, node: &Node, scrutinee: &Node, arms: &[(Pattern, Node)]) {
+        self.lint_match(node, arms);
+        self.compile(scrutinee);
+        let Some(slot) = self.add_local(None, scrutinee.span) else {
+            return;
+        };
+        let mut ends = Vec::new();
+        let mut exhaustive = false;
+        for (pattern, body) in arms {
+            match &pattern.kind {
+                PatternKind::Literal(literal) => {
+                    self.chunk().write_op_with_u8(OpCode::GetLocal, slot);
+                    self.compile_above(1, literal);
+                    self.chunk().write_op(OpCode::Equal);
+                    let skip = self.chunk().write_jump(OpCode::JumpIfFalse);
+                    self.compile(body);
+                    ends.push((self.chunk().write_jump(OpCode::Jump), body.span));
+                    self.patch_jump(skip, body.span);
+                }
+                PatternKind::Binding(name) => {
+                    self.locals.last_mut().unwrap().name = Some(*name);
+                    self.compile(body);
+                    self.locals.last_mut().unwrap().name = None;
+                    exhaustive = true;
+                    break;
+                }
+                PatternKind::Wildcard => {
+                    self.compile(body);
+                    exhaustive = true;
+                    break;
+                }
+            }
+        }
+        if !exhaustive {
+            self.chunk().write_op(OpCode::None);
+        }
+        for (end, span) in ends {
+            self.patch_jump(end, span);
+        }
+        // Drop the hidden local from under the result.
+        self.chunk().write_op(OpCode::Swap);
+        self.chunk().write_op(OpCode::Pop);
+        self.held = self.locals.pop().unwrap().held;
+    }
+
+    /// Starts a loop for `break`s to leave, with the locals declared so far
+    /// still on the stack where they land.
+    fn begin_loop(&mut self, yields_value: bool) {
+        self.loops.push(LoopContext {
+            breaks: Vec::new(),
+            height: self.stack_height(),
+            yields_value,
+        });
+    }
+
+    /// Ends the innermost loop, pointing its `break`s at the next
+    /// instruction.
+    fn end_loop(&mut self, span: Span) {
+        let context = self.loops.pop().expect("No loop to patch the breaks of");
+        for jump in context.breaks {
+            self.patch_jump(jump, span);
+        }
+    }
+
+    fn compile_break(&mut self, node: &Node, value: Option<&Node>) {
+        let Some(context) = self.loops.last() else {
+            self.reporter.report(
+                BreakOutsideLoop
+                    .make_labeled(node.span.labeled("Not inside a loop"))
+                    .finish()
+                    .into(),
+            );
+            return;
+        };
+        let (height, yields_value) = (context.height, context.yields_value);
+        match value {
+            Some(value) if !yields_value => {
+                self.reporter.report(
+                    BreakWithValue
+                        .make_labeled(value.span.labeled("This loop can't produce a value"))
+                        .with_help("Only `loop` can, so `loop` with an `if` and a `break` could replace it")
+                        .finish()
+                        .into(),
+                );
+                return;
             }
-            _ => (),
+            Some(value) => self.compile(value),
+            None if yields_value => self.chunk().write_op(OpCode::None),
+            None => (),
         }
+        // Pop what was pushed inside the loop, from under the value if there
+        // is one.
+        for _ in height..self.stack_height() {
+            if yields_value {
+                self.chunk().write_op(OpCode::Swap);
+            }
+            self.chunk().write_op(OpCode::Pop);
+        }
+        let jump = self.chunk().write_jump(OpCode::Jump);
+        self.loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    /// Advises about the branches of an `if` used as a statement that end
+    /// with a name or a literal, whose value is thrown away with the rest of
+    /// the `if`'s. It was most likely meant to be used.
+    fn lint_discarded_branches(&mut self, branches: &[(Node, Node)], otherwise: Option<&Node>) {
+        let blocks = branches.iter().map(|(_, then)| then).chain(otherwise);
+        for block in blocks {
+            let NodeKind::Block(stmts) = &block.kind else {
+                continue;
+            };
+            let Some(last) = stmts.last() else {
+                continue;
+            };
+            if let NodeKind::Identifier(_)
+            | NodeKind::IntegerLiteral(_)
+            | NodeKind::FloatLiteral(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::BooleanLiteral(_)
+            | NodeKind::NoneLiteral = last.kind
+            {
+                self.reporter.report(
+                    DiscardedValue
+                        .make_labeled(last.span.labeled("This value is discarded"))
+                        .with_note(
+                            "The `if` is a statement, so the value of its branch goes unused",
+                        )
+                        .with_help("Assign the `if` to a variable to use its value")
+                        .finish()
+                        .into(),
+                );
+            }
+        }
+    }
+
+    /// Warns about a `match` with no arms, or with arms that can't be
+    /// reached because an earlier one matches everything.
+    fn lint_match(&mut self, node: &Node, arms: &[(Pattern, Node)]) {
+        if arms.is_empty() {
+            self.reporter.report(
+                EmptyMatch
+                    .make_labeled(node.span.labeled("Has no arms"))
+                    .with_note("It always evaluates to None")
+                    .finish()
+                    .into(),
+            );
+            return;
+        }
+        let Some(catch_all) = arms
+            .iter()
+            .position(|(pattern, _)| pattern.is_irrefutable())
+        else {
+            return;
+        };
+        let Some(((first, _), rest)) = arms[catch_all + 1..].split_first() else {
+            return;
+        };
+        let last = rest.last().map_or(&arms[catch_all + 1].1, |(_, body)| body);
+        self.reporter.report(
+            UnreachableArm
+                .make_labeled(first.span.extend(last.span).labeled("Never reached"))
+                .with_label(
+                    arms[catch_all]
+                        .0
+                        .span
+                        .labeled("Since this matches every value"),
+                )
+                .finish()
+                .into(),
+        );
+    }
+
+    /// Whether compiling `node` leaves a value on the stack, which is
+    /// popped when it's a statement.
+    fn leaves_value(node: &Node) -> bool {
+        !matches!(
+            node.kind,
+            NodeKind::Return(_)
+                | NodeKind::Block(_)
+                | NodeKind::VarDeclaration(_, _)
+                | NodeKind::VarDestructure(_, _, _)
+                | NodeKind::Import(_, _)
+                | NodeKind::While(_, _)
+                | NodeKind::Break(_)
+                | NodeKind::For(_, _, _)
+        )
+    }
+
+    /// Compiles `node` as a statement, discarding its value if it's an
+    /// expression.
+    pub fn compile_statement(&mut self, node: &Node) {
+        self.chunk().mark_statement(node.span);
+        if let NodeKind::If(branches, otherwise) = &node.kind {
+            self.lint_discarded_branches(branches, otherwise.as_deref());
+        }
+        self.compile(node);
+        if Self::leaves_value(node) {
+            self.chunk().write_op(OpCode::Pop);
+        }
+    }
+
+    /// Compiles a block for its value, as a `do` block or a branch of an
+    /// `if`. It evaluates to its last statement if that's an expression, and
+    /// to `None` otherwise.
+    fn compile_block_value(&mut self, block: &Node) {
+        let NodeKind::Block(stmts) = &block.kind else {
+            unreachable!("Only blocks have a value as a block")
+        };
+        let value = stmts.last().filter(|last| Self::leaves_value(last));
+        let stmts = match value {
+            Some(_) => &stmts[..stmts.len() - 1],
+            None => &stmts[..],
+        };
+        self.begin_scope();
+        for stmt in stmts {
+            self.compile_statement(stmt);
+        }
+        match value {
+            Some(value) => {
+                self.chunk().mark_statement(value.span);
+                self.compile(value);
+            }
+            None => self.chunk().write_op(OpCode::None),
+        }
+        self.end_scope_with_value();
     }
 
     pub fn compile(&mut self, node: &Node) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
         match &node.kind {
             NodeKind::Return(val) => {
-                self.compile(val);
-                self.chunk.write_op(OpCode::Return);
+                match val {
+                    Some(val) => self.compile(val),
+                    None => self.chunk().write_op(OpCode::None),
+                }
+                self.chunk().write_op(OpCode::Return);
+            }
+            NodeKind::Block(stmts) => {
+                self.begin_scope();
+                for stmt in stmts {
+                    self.compile_statement(stmt);
+                }
+                self.end_scope();
+            }
+            NodeKind::VarDeclaration(name, val) => {
+                // The value is compiled before the name is in scope, so
+                // `let x = x` reads an outer `x`.
+                match val {
+                    Some(val) => self.compile(val),
+                    None => self.chunk().write_op(OpCode::None),
+                }
+                self.declare(*name, node.span);
+            }
+            NodeKind::VarDestructure(targets, rest, value) => {
+                self.compile(value);
+                let op = match rest {
+                    Some(_) => OpCode::UnpackRest,
+                    None => OpCode::Unpack,
+                };
+                self.chunk().write_op_with_u8(op, targets.len() as u8);
+                let targets = targets.iter().chain(rest);
+                if self.scope_depth == 0 {
+                    // Defining a global pops its value, so the last element
+                    // is bound first.
+                    for target in targets.rev() {
+                        match target.kind {
+                            PatternKind::Binding(name) => self.declare(name, target.span),
+                            _ => self.chunk().write_op(OpCode::Pop),
+                        }
+                    }
+                } else {
+                    // An element bound to `_` keeps its slot, unnamed.
+                    for target in targets {
+                        let name = match target.kind {
+                            PatternKind::Binding(name) => Some(name),
+                            _ => None,
+                        };
+                        self.add_local(name, target.span);
+                    }
+                }
+            }
+            NodeKind::Import(name, path) => {
+                if let Some(chunk) = self.compile_module(path, node.span) {
+                    self.chunk().write_op_with_u16(OpCode::Import, chunk);
+                    self.declare(*name, node.span);
+                }
+            }
+            NodeKind::If(branches, otherwise) => {
+                // Each branch jumps straight to the end of the whole chain
+                // once it has run, with its value. With no `else`, the chain
+                // evaluates to `None` when no branch runs.
+                let mut ends = Vec::new();
+                for (cond, then) in branches {
+                    self.compile(cond);
+                    let skip_then = self.chunk().write_jump(OpCode::JumpIfFalse);
+                    self.compile_block_value(then);
+                    ends.push((self.chunk().write_jump(OpCode::Jump), then.span));
+                    self.patch_jump(skip_then, then.span);
+                }
+                match otherwise {
+                    Some(otherwise) => self.compile_block_value(otherwise),
+                    None => self.chunk().write_op(OpCode::None),
+                }
+                for (end, span) in ends {
+                    self.patch_jump(end, span);
+                }
+            }
+            NodeKind::Conditional(cond, then, otherwise) => {
+                self.compile(cond);
+                let skip_then = self.chunk().write_jump(OpCode::JumpIfFalse);
+                self.compile(then);
+                let skip_else = self.chunk().write_jump(OpCode::Jump);
+                self.patch_jump(skip_then, then.span);
+                self.compile(otherwise);
+                self.patch_jump(skip_else, otherwise.span);
+            }
+            NodeKind::While(cond, body) => {
+                let start = self.chunk().source.len();
+                self.compile(cond);
+                let exit = self.chunk().write_jump(OpCode::JumpIfFalse);
+                self.begin_loop(false);
+                self.compile(body);
+                if self.chunk().write_loop(start).is_none() {
+                    self.report_jump_too_far(body.span);
+                }
+                self.patch_jump(exit, body.span);
+                self.end_loop(body.span);
+            }
+            NodeKind::Loop(body) => {
+                let start = self.chunk().source.len();
+                self.begin_loop(true);
+                self.compile(body);
+                if self.chunk().write_loop(start).is_none() {
+                    self.report_jump_too_far(body.span);
+                }
+                self.end_loop(body.span);
+            }
+            NodeKind::Break(value) => self.compile_break(node, value.as_deref()),
+            NodeKind::Do(body) => self.compile_block_value(body),
+            NodeKind::For(ident, range, body) => {
+                // The range is evaluated once, before the loop variable
+                // comes into scope. The integer after its last is kept in a
+                // hidden local.
+                self.begin_scope();
+                self.compile(range);
+                self.chunk().write_op(OpCode::RangeBounds);
+                let Some(counter) = self.add_local(Some(*ident), range.span) else {
+                    self.end_scope();
+                    return;
+                };
+                let Some(limit) = self.add_local(None, range.span) else {
+                    self.end_scope();
+                    return;
+                };
+                let loop_start = self.chunk().source.len();
+                self.chunk().write_op_with_u8(OpCode::GetLocal, counter);
+                self.chunk().write_op_with_u8(OpCode::GetLocal, limit);
+                self.chunk().write_op(OpCode::Less);
+                let exit = self.chunk().write_jump(OpCode::JumpIfFalse);
+                self.begin_loop(false);
+                self.compile(body);
+                self.chunk().write_op_with_u8(OpCode::GetLocal, counter);
+                self.write_const(Value::Integer(1), range.span);
+                self.chunk().write_op(OpCode::Add);
+                self.chunk().write_op_with_u8(OpCode::SetLocal, counter);
+                self.chunk().write_op(OpCode::Pop);
+                if self.chunk().write_loop(loop_start).is_none() {
+                    self.report_jump_too_far(body.span);
+                }
+                self.patch_jump(exit, body.span);
+                self.end_loop(body.span);
+                self.end_scope();
+            }
+            NodeKind::Assignment(op, target, value) => {
+                let NodeKind::Identifier(name) = &target.kind else {
+                    unreachable!("The parser only allows assigning to identifiers")
+                };
+                let Some(var) = self.resolve(*name) else {
+                    self.reporter.report(
+                        UndefinedVariable(*name)
+                            .make_labeled(target.span.labeled("Assigned before being declared"))
+                            .with_help(format!(
+                                "Use `let {} = ...` to declare a new variable",
+                                name
+                            ))
+                            .finish()
+                            .into(),
+                    );
+                    return;
+                };
+                if let Some(op) = op {
+                    self.write_get(var, target.span);
+                    self.compile_above(1, value);
+                    self.handle_binary_op(op);
+                } else {
+                    self.compile(value);
+                }
+                self.write_set(var, target.span);
             }
-            NodeKind::Block(_) => unimplemented!("awaiting scopes"),
-            NodeKind::VarDeclaration(_, _) => unimplemented!("awaiting scopes"),
             NodeKind::UnaryOperation(op, val) => {
                 self.compile(val);
-                self.chunk.write_op(match op {
+                self.chunk().write_op(match op {
+                    // Unary plus leaves its operand as it is.
+                    Operator::Plus => return,
+                    Operator::Minus => OpCode::Negate,
                     Operator::Not => OpCode::Not,
+                    Operator::BitNot => OpCode::BitNot,
                     _ => unreachable!(),
                 })
             }
+            NodeKind::BinaryOperation(op @ (Operator::And | Operator::Or), lhs, rhs) => {
+                self.compile_logical(op, lhs, rhs)
+            }
+            NodeKind::BinaryOperation(Operator::Coalesce, lhs, rhs) => {
+                self.compile_coalesce(lhs, rhs)
+            }
             NodeKind::BinaryOperation(op, lhs, rhs) => {
-                // The parser will always parse compound expressions such that:
-                // lhs will be either a value or another compound node
-                // rhs will never be another compound node
-                // Knowing this, we can handle the case where we are compiling
-                // a compound expression specifically.
                 self.compile(lhs);
-                match &lhs.kind {
-                    NodeKind::BinaryOperation(l_op, _, l_rhs)
-                        if op.is_compound() && l_op.is_compound() =>
-                    {
-                        // We know we are compiling a compound expression
-                        // i.e. 1 < 2 < 3
-                        // Since we have already compiled the lhs, we now
-                        // take the lhs node's rhs, and use it to compile
-                        // our operator.
-                        self.compile(&l_rhs);
-                        self.compile(rhs);
-                        self.handle_binary_op(op);
-                        // Now by writing and, we effectively turn the above example
-                        // into (1 < 2) and (2 < 3)
-                        self.chunk.write_op(OpCode::And);
-                    }
-                    _ => {
-                        self.compile(rhs);
-                        self.handle_binary_op(op);
+                self.compile_above(1, rhs);
+                self.handle_binary_op(op);
+            }
+            NodeKind::Comparison(first, rest) => self.compile_comparison(first, rest),
+            NodeKind::Match(scrutinee, arms) => self.compile_match(node, scrutinee, arms),
+            NodeKind::Range(start, end, inclusive) => {
+                self.check_range(start, end);
+                self.compile(start);
+                self.compile_above(1, end);
+                self.chunk()
+                    .write_op_with_u8(OpCode::BuildRange, *inclusive as u8);
+            }
+            NodeKind::Index(target, index) => {
+                self.compile(target);
+                self.compile_above(1, index);
+                self.chunk().write_op(OpCode::Index);
+            }
+            NodeKind::MemberAccess(target, name) => {
+                // Until there are other kinds of members, `a.b` is `a["b"]`.
+                self.compile(target);
+                let name = self.program.intern(name.as_str());
+                self.write_const(Value::String(name), node.span);
+                self.chunk().write_op(OpCode::Index);
+            }
+            NodeKind::Slice(target, start, end) => {
+                self.compile(target);
+                for (held, bound) in [start, end].into_iter().enumerate() {
+                    match bound {
+                        Some(bound) => self.compile_above(held + 1, bound),
+                        None => self.chunk().write_op(OpCode::None),
                     }
                 }
+                self.chunk().write_op(OpCode::Slice);
+            }
+            NodeKind::ListLiteral(items) => {
+                for (held, item) in items.iter().enumerate() {
+                    self.compile_above(held, item);
+                }
+                match u16::try_from(items.len()) {
+                    Ok(count) => self.chunk().write_op_with_u16(OpCode::BuildList, count),
+                    Err(_) => self.reporter.report(
+                        TooManyItems
+                            .make_labeled(node.span.label())
+                            .with_note(format!(
+                                "A list literal can hold at most {} items",
+                                u16::MAX
+                            ))
+                            .finish()
+                            .into(),
+                    ),
+                }
+            }
+            NodeKind::MapLiteral(entries) => {
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    self.compile_above(i * 2, key);
+                    self.compile_above(i * 2 + 1, value);
+                }
+                match u16::try_from(entries.len()) {
+                    Ok(count) => self.chunk().write_op_with_u16(OpCode::BuildMap, count),
+                    Err(_) => self.reporter.report(
+                        TooManyItems
+                            .make_labeled(node.span.label())
+                            .with_note(format!(
+                                "A map literal can hold at most {} entries",
+                                u16::MAX
+                            ))
+                            .finish()
+                            .into(),
+                    ),
+                }
+            }
+            NodeKind::Identifier(name) => match self.resolve(*name) {
+                Some(var) => self.write_get(var, node.span),
+                None => self.reporter.report(
+                    UndefinedVariable(*name)
+                        .make_labeled(node.span.labeled("Not declared in this scope"))
+                        .with_help(format!("Declare it first with `let {} = ...`", name))
+                        .finish()
+                        .into(),
+                ),
+            },
+            NodeKind::StringLiteral(val) => {
+                let val = self.program.intern(val);
+                self.write_const(Value::String(val), node.span)
+            }
+            NodeKind::FloatLiteral(val) => self.write_const(Value::Float(*val), node.span),
+            NodeKind::IntegerLiteral(val) => self.write_const(Value::Integer(*val), node.span),
+            NodeKind::BooleanLiteral(val) => self.write_const(Value::Boolean(*val), node.span),
+            NodeKind::NoneLiteral => self.chunk().write_op(OpCode::None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelToken;
+    use crate::pipeline::compile_source;
+    use crate::pipeline::testing::{compile, compile_err, eval, path_name, run, temp_dir, titles};
+    use crate::report::Report;
+    use crate::vm::{VMError, VMResult};
+
+    #[test]
+    fn and_skips_rhs_when_lhs_is_falsy() {
+        let source = "let x = 0\nreturn x != 0 and 10 / x > 1";
+        assert_eq!(run(source), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn or_skips_rhs_when_lhs_is_truthy() {
+        let source = "let x = 0\nreturn x == 0 or 10 / x > 1";
+        assert_eq!(run(source), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn logical_operators_evaluate_rhs_when_undecided() {
+        assert_eq!(
+            eval("let x = 5\nreturn x != 0 and 10 / x > 1.0"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            eval("let x = 5\nreturn x == 1 or 10 / x > 1.0"),
+            Value::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn logical_operators_return_the_deciding_operand() {
+        // (operand, whether it's truthy)
+        let operands = [
+            ("None", false),
+            ("False", false),
+            ("True", true),
+            ("0", false),
+            ("7", true),
+            ("0.0", false),
+            ("0.5", true),
+            ("\"\"", false),
+            ("\"a\"", true),
+            ("[]", false),
+            ("[0]", true),
+            ("{}", false),
+            ("{1: 2}", true),
+            ("1..1", false),
+            ("1..=1", true),
+        ];
+        for (lhs, lhs_truthy) in operands {
+            for (rhs, _) in operands {
+                let and = if lhs_truthy { rhs } else { lhs };
+                let or = if lhs_truthy { lhs } else { rhs };
+                for (op, expected) in [("and", and), ("or", or)] {
+                    let source = format!("return {lhs} {op} {rhs}");
+                    assert_eq!(
+                        eval(&source).to_string(),
+                        eval(&format!("return {expected}")).to_string(),
+                        "{source}"
+                    );
+                }
             }
-            NodeKind::Identifier(_) => unimplemented!("awaiting var declaration"),
-            NodeKind::StringLiteral(val) => self.chunk.write_const(Value::String(val.clone())),
-            NodeKind::FloatLiteral(val) => self.chunk.write_const(Value::Float(*val)),
-            NodeKind::IntegerLiteral(val) => self.chunk.write_const(Value::Integer(*val as isize)),
-            NodeKind::BooleanLiteral(val) => self.chunk.write_const(Value::Boolean(*val)),
         }
     }
+
+    #[test]
+    fn conditions_arent_tested_for_truthiness() {
+        let branch_on = |lhs| VMError::TypeError {
+            op: "branch on",
+            lhs,
+            rhs: None,
+        };
+        assert_eq!(run("if 0 {}"), Err(branch_on("Integer")));
+        assert_eq!(run("while \"\" {}"), Err(branch_on("String")));
+        assert_eq!(run("return if None then 1 else 2"), Err(branch_on("None")));
+        // Unlike `!`, which a condition can use to test one.
+        assert_eq!(eval("if not 0 { return 1 }"), Value::Integer(1));
+        assert_eq!(eval("return if not [] then 1 else 2"), Value::Integer(1));
+    }
+
+    #[test]
+    fn while_with_an_empty_body() {
+        let source = "let i = 0\nwhile (i += 1) < 5 {}\nreturn i";
+        assert_eq!(eval(source), Value::Integer(5));
+    }
+
+    #[test]
+    fn while_with_a_false_condition() {
+        let source = "let out = 1\nwhile False { out = 2 }\nreturn out";
+        assert_eq!(eval(source), Value::Integer(1));
+    }
+
+    #[test]
+    fn return_inside_while() {
+        let source = "let i = 0\nwhile True {\n    i += 1\n    if i == 3 { return i }\n}";
+        assert_eq!(eval(source), Value::Integer(3));
+    }
+
+    #[test]
+    fn for_over_an_empty_range() {
+        let source = "let n = 0\nfor i in 3..3 { n += 1 }\nreturn n";
+        assert_eq!(eval(source), Value::Integer(0));
+        let source = "let a = 3\nlet n = 0\nfor i in a..a { n += 1 }\nreturn n";
+        assert_eq!(eval(source), Value::Integer(0));
+    }
+
+    #[test]
+    fn for_over_a_descending_range() {
+        // Caught at compile time when both ends are literals, and at
+        // runtime otherwise.
+        assert_eq!(titles(&compile_err("for i in 5..2 {}")), ["InvalidRange"]);
+        let source = "let a = 5\nfor i in a..2 {}";
+        assert_eq!(
+            run(source),
+            Err(VMError::ReversedRange { start: 5, end: 2 })
+        );
+    }
+
+    #[test]
+    fn for_with_no_slots_left_ends_its_scope() {
+        let mut source = "do {\n".to_string();
+        for i in 0..Compiler::MAX_LOCALS {
+            source += &format!("let a{i} = 0\n");
+        }
+        source += "for i in 0..3 { }\n}\nlet after = 1\nreturn after";
+        assert_eq!(titles(&compile_err(&source)), ["TooManyLocals"]);
+    }
+
+    #[test]
+    fn comparisons_chain() {
+        assert_eq!(eval("return 1 < 2 < 3"), Value::Boolean(true));
+        assert_eq!(eval("return 1 < 3 < 2"), Value::Boolean(false));
+        assert_eq!(eval("return 3 > 2 >= 2 > 1"), Value::Boolean(true));
+        assert_eq!(eval("return 1 <= 1 < 1"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn chained_operands_are_evaluated_once() {
+        let source = "let n = 0\nlet b = 1 < do { n = n + 1\n2 } < 3\nreturn [b, n]";
+        assert_eq!(eval(source).to_string(), "[true, 1]");
+    }
+
+    #[test]
+    fn chains_stop_at_the_first_false_comparison() {
+        let source = "let n = 0\nlet b = 2 < 1 < do { n = n + 1\n3 }\nreturn [b, n]";
+        assert_eq!(eval(source).to_string(), "[false, 0]");
+    }
+
+    #[test]
+    fn parenthesized_comparisons_dont_chain() {
+        assert_eq!(
+            run("return (1 < 2) < 3"),
+            Err(VMError::TypeError {
+                op: "compare",
+                lhs: "Boolean",
+                rhs: Some("Integer"),
+            })
+        );
+    }
+
+    /// The types of the operands a failing comparison was given at runtime,
+    /// in the order it was given them.
+    fn observed_operands(source: &str) -> (&'static str, &'static str) {
+        match run(source) {
+            Err(VMError::TypeError {
+                lhs,
+                rhs: Some(rhs),
+                ..
+            }) => (lhs, rhs),
+            result => panic!("{source} gave {result:?}"),
+        }
+    }
+
+    #[test]
+    fn comparison_operand_order() {
+        // (operator, whether the runtime sees the operands swapped). `in`
+        // isn't swapped, but its error names the container it searched first.
+        let operators = [
+            ("<", false),
+            ("<=", false),
+            (">", true),
+            (">=", true),
+            ("in", true),
+            ("not in", true),
+        ];
+        let operands = [
+            (("1", "Integer"), ("\"a\"", "String")),
+            (("\"a\"", "String"), ("1", "Integer")),
+            (("1.5", "Float"), ("2", "Integer")),
+            (("None", "None"), ("[1]", "List")),
+        ];
+        for (op, swapped) in operators {
+            for ((lhs, lhs_type), (rhs, rhs_type)) in operands {
+                let source = format!("return {lhs} {op} {rhs}");
+                if op.ends_with("in") && rhs_type == "List" {
+                    // A list can be searched for anything.
+                    assert_eq!(run(&source).map(|_| ()), Ok(()), "{source}");
+                    continue;
+                }
+                let expected = if swapped {
+                    (rhs_type, lhs_type)
+                } else {
+                    (lhs_type, rhs_type)
+                };
+                assert_eq!(observed_operands(&source), expected, "{source}");
+            }
+        }
+    }
+
+    #[test]
+    fn comparison_operands_are_evaluated_left_to_right() {
+        for op in ["<", "<=", ">", ">=", "==", "!=", "in", "not in"] {
+            let rhs = if op.ends_with("in") { "[2]" } else { "2" };
+            let source = format!(
+                "let n = 0\nlet r = do {{ n = n * 10 + 1\n1 }} {op} do {{ n = n * 10 + 2\n{rhs} }}\nreturn n"
+            );
+            assert_eq!(eval(&source), Value::Integer(12), "{source}");
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn modules_are_compiled_once_by_canonical_path() {
+        let dir = temp_dir();
+        std::fs::write(dir.join("a.moon"), "let x = 1").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("link")).unwrap();
+        let source = "import a\nimport link.a as b\nreturn [a.x, b.x]".to_string();
+        let main = path_name(dir.join("main.ml"));
+        let Ok((program, _)) =
+            compile_source(main, source, &VmOptions::default(), &CancelToken::new())
+        else {
+            panic!("Failed to compile");
+        };
+        assert_eq!(program.chunks.len(), 2);
+        let value = crate::pipeline::run(&program, &VmOptions::default(), &CancelToken::new());
+        assert_eq!(
+            value.map(|value| value.to_string()),
+            Ok("[1, 1]".to_string())
+        );
+    }
+
+    /// Writes `a.moon` and `b.moon`, and runs a script next to them that
+    /// imports `a`. Returns the paths of the modules as they're reported,
+    /// the result, and the reports from compiling it.
+    fn run_modules(a: &str, b: &str) -> ([String; 2], VMResult<Value>, Vec<Report>) {
+        let dir = temp_dir();
+        std::fs::write(dir.join("a.moon"), a).unwrap();
+        std::fs::write(dir.join("b.moon"), b).unwrap();
+        let source = "import a\nreturn a".to_string();
+        let main = path_name(dir.join("main.ml"));
+        let options = VmOptions::default();
+        let Ok((program, diagnostics)) =
+            compile_source(main, source, &options, &CancelToken::new())
+        else {
+            panic!("Failed to compile");
+        };
+        let value = crate::pipeline::run(&program, &options, &CancelToken::new());
+        let paths = ["a.moon", "b.moon"].map(|name| dir.join(name).display().to_string());
+        (paths, value, diagnostics.reports)
+    }
+
+    #[test]
+    fn mutual_imports() {
+        let ([a, b], value, reports) =
+            run_modules("import b\nlet x = b.y + 1", "let y = 1\nimport a");
+        // `a` sees all of `b`, which only saw `a` as it was when `b` was
+        // imported.
+        assert_eq!(
+            value.map(|value| value.to_string()),
+            Ok("{\"b\": {\"y\": 1, \"a\": {...}}, \"x\": 2}".to_string())
+        );
+        let [report] = &reports[..] else {
+            panic!("Expected one report, got {}", reports.len());
+        };
+        assert_eq!(report.title(), "ImportCycle");
+        assert_eq!(report.level, ReportLevel::Advice);
+        let cycle = format!("The cycle is {a} -> {b} -> {a}");
+        assert_eq!(report.note(), Some(cycle.as_str()));
+        let help =
+            format!("Until it finishes loading, {a} only has the names defined above its imports");
+        assert_eq!(report.help(), Some(help.as_str()));
+        // One on each import statement in the cycle.
+        let labels: Vec<_> = report
+            .labels()
+            .into_iter()
+            .map(|(span, message)| (span.filename.to_string(), message.unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                (
+                    b.clone(),
+                    format!("Imports {a} before it has finished loading")
+                ),
+                (a.clone(), format!("Imports {b}")),
+            ]
+        );
+    }
+
+    #[test]
+    fn mutual_imports_reading_a_name_too_early() {
+        let ([a, b], value, _) = run_modules("import b\nlet x = 1", "import a\nlet y = a.x");
+        assert_eq!(
+            value,
+            Err(VMError::NotYetDefined {
+                name: "x".into(),
+                cycle: format!("{a} -> {b} -> {a}")
+            })
+        );
+    }
+
+    #[test]
+    fn equality_doesnt_chain() {
+        // As a chain this would be `1 == 1 and 1 == True`.
+        assert_eq!(eval("return 1 == 1 == True"), Value::Boolean(true));
+        assert_eq!(eval("return 2 != 3 != True"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn first_matching_arm_wins() {
+        let source = "let x = 2\nreturn match x { 1 => 10, 2 => 20, 2 => 30, _ => 40 }";
+        assert_eq!(eval(source), Value::Integer(20));
+    }
+
+    #[test]
+    fn match_falls_through_to_catch_all() {
+        assert_eq!(
+            eval("return match 5 { 1 => 10, _ => 40 }"),
+            Value::Integer(40)
+        );
+        assert_eq!(
+            eval("return match 5 { 1 => 10, n => n * 2 }"),
+            Value::Integer(10)
+        );
+        assert_eq!(eval("return match 5 { 1 => 10 }"), Value::None);
+    }
+
+    #[test]
+    fn unreachable_arms_are_linted() {
+        let diagnostics = compile("return match 1 { _ => 1, 2 => 2, 3 => 3 }");
+        assert_eq!(titles(&diagnostics), ["UnreachableArm"]);
+        let diagnostics = compile("return match 1 { 2 => 2, n => n }");
+        assert!(titles(&diagnostics).is_empty());
+    }
+
+    #[test]
+    fn top_level_match() {
+        // The matched value's hidden local sits at scope depth 0, alongside
+        // the globals, and must be gone before the next statement.
+        let source = "let a = 1\nlet m = match a { 1 => 10, _ => 0 }\nlet b = 2\nreturn [a, m, b]";
+        assert_eq!(eval(source).to_string(), "[1, 10, 2]");
+        let source = "match 1 { 1 => 10 }\nlet b = 2\nreturn b";
+        assert_eq!(eval(source), Value::Integer(2));
+    }
+
+    #[test]
+    fn match_between_locals() {
+        let source = "return do {\nlet a = 1\nlet m = match a { 1 => 10 }\nlet b = 2\n[a, m, b]\n}";
+        assert_eq!(eval(source).to_string(), "[1, 10, 2]");
+    }
+
+    #[test]
+    fn if_without_else() {
+        let source = "let out = 0\nif True { out = 1 }\nif False { out = 2 }\nreturn out";
+        assert_eq!(eval(source), Value::Integer(1));
+    }
+
+    #[test]
+    fn nested_ifs() {
+        for (a, b, expected) in [
+            ("True", "True", 1),
+            ("True", "False", 2),
+            ("False", "True", 3),
+            ("False", "False", 3),
+        ] {
+            let source = format!(
+                "if {a} {{\n    if {b} {{ return 1 }} else {{ return 2 }}\n}} else {{\n    return 3\n}}"
+            );
+            assert_eq!(eval(&source), Value::Integer(expected), "{source}");
+        }
+    }
+
+    #[test]
+    fn non_boolean_if_condition() {
+        let err = run("if \"yes\" { return 1 }").unwrap_err();
+        assert_eq!(err.to_string(), "Cannot branch on String");
+        // The second condition is never evaluated.
+        assert_eq!(run("if True {} elif [1] {}"), Ok(Value::None));
+        let err = run("if False {} elif [1] {}").unwrap_err();
+        assert_eq!(err.to_string(), "Cannot branch on List");
+    }
+
+    #[test]
+    fn else_if_chain_runs_only_the_first_true_branch() {
+        let source = "let out = 0
+for i in 0..6 {
+    if i == 0 { out = out * 10 + 1 }
+    else if i == 1 { out = out * 10 + 2 }
+    elif i == 2 { out = out * 10 + 3 }
+    elif i < 5 { out = out * 10 + 4 }
+    else { out = out * 10 + 5 }
+}
+return out";
+        assert_eq!(eval(source), Value::Integer(123445));
+    }
+
+    #[test]
+    fn else_if_chain_skips_later_conditions() {
+        let source = "let x = if True { 1 } elif 1 // 0 == 0 { 2 } else { 3 }\nreturn x";
+        assert_eq!(eval(source), Value::Integer(1));
+    }
+
+    #[test]
+    fn else_if_chain_without_else() {
+        let source = "let out = 0\nif False { out = 1 } elif False { out = 2 }\nreturn out";
+        assert_eq!(eval(source), Value::Integer(0));
+        let source = "let x = if False { 1 } elif False { 2 }\nreturn x";
+        assert_eq!(eval(source), Value::None);
+    }
+
+    #[test]
+    fn destructuring() {
+        let source = "let [a, b] = [1, 2]\nreturn [b, a]";
+        assert_eq!(eval(source).to_string(), "[2, 1]");
+        let source = "return do {\nlet a, b = [1, 2]\n[b, a]\n}";
+        assert_eq!(eval(source).to_string(), "[2, 1]");
+    }
+
+    #[test]
+    fn destructuring_skips_underscores() {
+        let source = "let _, b, _ = [1, 2, 3]\nreturn b";
+        assert_eq!(eval(source), Value::Integer(2));
+    }
+
+    #[test]
+    fn destructuring_collects_the_rest() {
+        let source = "let [a, ..rest] = [1, 2, 3]\nreturn [a, rest]";
+        assert_eq!(eval(source).to_string(), "[1, [2, 3]]");
+        let source = "let a, ..rest = [1]\nreturn rest";
+        assert_eq!(eval(source).to_string(), "[]");
+        let source = "let a, .._ = [1, 2]\nreturn a";
+        assert_eq!(eval(source), Value::Integer(1));
+    }
+
+    #[test]
+    fn destructuring_arity_mismatch() {
+        let cases = [
+            ("let a, b = [1, 2, 3]", 2, 3, false),
+            ("let a, b = []", 2, 0, false),
+            ("let a, b, ..rest = [1]", 2, 1, true),
+        ];
+        for (source, expected, found, rest) in cases {
+            assert_eq!(
+                run(source),
+                Err(VMError::DestructureLength {
+                    expected,
+                    found,
+                    rest,
+                }),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn nested_blocks_shadow_outer_locals() {
+        // Each block appends the `x` it sees as two more digits.
+        let source = "do {
+    let x = 1
+    let seen = 0
+    do {
+        let x = x + 1
+        do {
+            let x = x * 10
+            seen = seen * 100 + x
+        }
+        seen = seen * 100 + x
+    }
+    seen = seen * 100 + x
+    return seen
+}";
+        assert_eq!(eval(source), Value::Integer(20_02_01));
+    }
+
+    #[test]
+    fn locals_end_with_their_block() {
+        let diagnostics = compile_err("do {\n    do { let x = 1 }\n    return x\n}");
+        assert_eq!(titles(&diagnostics), ["UndefinedVariable `x`"]);
+    }
+
+    #[test]
+    fn chained_assignment() {
+        let source = "let a = 0\nlet b = 0\na = b = 1\nreturn [a, b]";
+        assert_eq!(eval(source).to_string(), "[1, 1]");
+        let source = "do {\n    let a = 0\n    let b = 0\n    a = b = 2\n    return [a, b]\n}";
+        assert_eq!(eval(source).to_string(), "[2, 2]");
+        let source = "let a = 0\ndo {\n    let b = 0\n    return [a = b = 3, a, b]\n}";
+        assert_eq!(eval(source).to_string(), "[3, 3, 3]");
+    }
+
+    #[test]
+    fn assigning_to_an_undeclared_variable() {
+        let diagnostics = compile_err("let a = 0\na = b = 1");
+        assert_eq!(titles(&diagnostics), ["UndefinedVariable `b`"]);
+        assert_eq!(
+            diagnostics.reports[0].help(),
+            Some("Use `let b = ...` to declare a new variable")
+        );
+    }
+
+    #[test]
+    fn compound_assignment() {
+        for (op, expected) in [("+=", 9), ("-=", 3), ("*=", 18), ("/=", 2)] {
+            let source = format!("let x = 6\nx {op} 3\nreturn x");
+            let expected = match op {
+                "/=" => Value::Float(expected as f64),
+                _ => Value::Integer(expected),
+            };
+            assert_eq!(eval(&source), expected, "{source}");
+            let source = format!("do {{\n    let x = 6\n    x {op} 3\n    return x\n}}");
+            assert_eq!(eval(&source), expected, "{source}");
+        }
+        assert_eq!(
+            eval("let s = \"a\"\ns += \"b\"\nreturn s").to_string(),
+            "ab"
+        );
+    }
+
+    #[test]
+    fn compound_assignment_to_an_undeclared_variable() {
+        let diagnostics = compile_err("x += 1");
+        assert_eq!(titles(&diagnostics), ["UndefinedVariable `x`"]);
+        let labels = diagnostics.reports[0].labels();
+        assert_eq!((labels[0].0.start, labels[0].0.end), (0, 1));
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        assert_eq!(
+            eval("let x = if True then 1 else 2\nreturn x"),
+            Value::Integer(1)
+        );
+        assert_eq!(eval("return if False then 1 else 2"), Value::Integer(2));
+        let source =
+            "let n = 5\nreturn if n < 0 then \"neg\" else if n == 0 then \"zero\" else \"pos\"";
+        assert_eq!(eval(source), Value::String("pos".into()));
+        let source = "return if True then if False then 1 else 2 else 3";
+        assert_eq!(eval(source), Value::Integer(2));
+    }
+
+    #[test]
+    fn conditional_precedence() {
+        // The else branch takes the whole `or`, rather than the conditional
+        // being its left operand.
+        assert_eq!(eval("return if True then 0 else 1 or 2"), Value::Integer(0));
+        assert_eq!(
+            eval("return if False or True then 1 else 2"),
+            Value::Integer(1)
+        );
+        assert_eq!(eval("return 0 or if True then 1 else 2"), Value::Integer(1));
+    }
+
+    #[test]
+    fn conditional_without_else() {
+        let diagnostics = compile_err("let x = if True then 1\nreturn x");
+        assert_eq!(
+            titles(&diagnostics),
+            ["SyntaxError Conditional expression without else"]
+        );
+    }
+
+    #[test]
+    fn loop_yields_its_break_value() {
+        let source =
+            "let i = 0\nlet x = loop {\n    i += 1\n    if i == 4 { break i * 10 }\n}\nreturn x";
+        assert_eq!(eval(source), Value::Integer(40));
+        // A plain `break` yields `None`.
+        assert_eq!(eval("return loop { break }"), Value::None);
+        // From a nested loop, only the inner one ends.
+        let source = "let x = loop {\n    let y = loop { break 1 }\n    break y + 1\n}\nreturn x";
+        assert_eq!(eval(source), Value::Integer(2));
+    }
+
+    #[test]
+    fn break_with_a_value_in_while_or_for() {
+        assert_eq!(
+            titles(&compile_err("while True { break 1 }")),
+            ["BreakWithValue"]
+        );
+        assert_eq!(
+            titles(&compile_err("for i in 0..3 { break i }")),
+            ["BreakWithValue"]
+        );
+        // A plain `break` is fine in either.
+        assert_eq!(
+            eval("let i = 0\nwhile True { i += 1\nbreak }\nreturn i"),
+            Value::Integer(1)
+        );
+    }
+
+    #[test]
+    fn do_block_evaluates_to_a_local_it_declared() {
+        // The local is popped by the end of the block's scope from under
+        // the copy that's its value.
+        let source = "let x = do {\nlet a = 1\nlet b = a + 1\nb\n}\nreturn x";
+        assert_eq!(eval(source), Value::Integer(2));
+        let source = "return do {\nlet a = 1\ndo {\nlet b = 2\nb\n} + a\n}";
+        assert_eq!(eval(source), Value::Integer(3));
+    }
+
+    #[test]
+    fn empty_block_evaluates_to_none() {
+        assert_eq!(eval("return do {}"), Value::None);
+        assert_eq!(eval("let x = if True {} else { 1 }\nreturn x"), Value::None);
+        assert_eq!(eval("return if False { 1 } else {}"), Value::None);
+    }
+
+    #[test]
+    fn block_ending_in_let_evaluates_to_none() {
+        assert_eq!(eval("return do { let a = 1 }"), Value::None);
+        let source = "let x = if True {\n    let a = 1\n    let b = a\n} else { 1 }\nreturn x";
+        assert_eq!(eval(source), Value::None);
+        // The locals are still popped, leaving the stack balanced for what
+        // follows.
+        let source = "let a = 1\nlet x = do { let b = 2 }\nreturn [a, x]";
+        assert_eq!(eval(source).to_string(), "[1, None]");
+    }
+
+    #[test]
+    fn coalesce_chains() {
+        assert_eq!(eval("return None ?? None ?? 3"), Value::Integer(3));
+        assert_eq!(eval("return None ?? 2 ?? 3"), Value::Integer(2));
+        assert_eq!(eval("return False ?? 1"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn coalesce_evaluates_lhs_once() {
+        let source = "let n = 0\nlet x = do { n = n + 1\n None } ?? 5\nreturn [x, n]";
+        assert_eq!(eval(source).to_string(), "[5, 1]");
+        let source = "let n = 0\nlet x = do { n = n + 1\n 4 } ?? 5\nreturn [x, n]";
+        assert_eq!(eval(source).to_string(), "[4, 1]");
+    }
+
+    #[test]
+    fn coalesce_skips_rhs() {
+        assert_eq!(run("return 1 ?? 1 // 0"), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn coalesce_precedence() {
+        // `1 ?? (0 and 5)`, rather than `(1 ?? 0) and 5`.
+        assert_eq!(eval("return 1 ?? 0 and 5"), Value::Integer(1));
+        // `(False ?? 2) or 3`, rather than `False ?? (2 or 3)`.
+        assert_eq!(eval("return False ?? 2 or 3"), Value::Integer(3));
+    }
 }
@@ -0,0 +1,279 @@
+//! Conversions between [`Value`] and Rust types, so natives can take and
+//! return plain Rust values instead of matching on `&[Value]` themselves.
+//!
+//! Nothing registers natives yet, so for now only the tests use these.
+#![cfg_attr(not(test), allow(dead_code))]
+use crate::vm::{VMError, VMResult, Value};
+use std::rc::Rc;
+
+pub trait FromValue: Sized {
+    /// The name of the expected type in errors, e.g. "Integer".
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "Integer";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// Integers are widened, the same way arithmetic mixes the two.
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "Float";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(v) => Some(*v),
+            Value::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const EXPECTED: &'static str = "Boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Rc<str> {
+    const EXPECTED: &'static str = "String";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "String";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Rc::<str>::from_value(value).map(|v| v.to_string())
+    }
+}
+
+/// `None` converts to `None`, anything else must convert to `T`.
+impl<T: FromValue> FromValue for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::None => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "Value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl IntoValue for Rc<str> {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::None, T::into_value)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::None
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+/// What a native can return: any [`IntoValue`], or a `Result` whose error
+/// becomes a runtime error carrying its message.
+pub trait IntoReturn {
+    fn into_return(self) -> VMResult<Value>;
+}
+
+impl<T: IntoValue> IntoReturn for T {
+    fn into_return(self) -> VMResult<Value> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue> IntoReturn for Result<T, String> {
+    fn into_return(self) -> VMResult<Value> {
+        self.map(T::into_value).map_err(VMError::Custom)
+    }
+}
+
+/// Converts the argument at `index` of a native taking `arity` arguments,
+/// with an error naming its position and expected type if it's missing or
+/// has the wrong type.
+pub fn argument<T: FromValue>(args: &[Value], index: usize, arity: usize) -> VMResult<T> {
+    let value = args.get(index).ok_or(VMError::ArgumentCount {
+        expected: arity,
+        found: args.len(),
+    })?;
+    T::from_value(value).ok_or_else(|| VMError::ArgumentType {
+        index,
+        expected: T::EXPECTED,
+        found: value.type_name(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert<T: FromValue>(value: Value) -> VMResult<T> {
+        argument(&[value], 0, 1)
+    }
+
+    fn convert_err<T: FromValue>(value: Value) -> VMError {
+        convert::<T>(value).map(|_| ()).unwrap_err()
+    }
+
+    #[test]
+    fn from_value() {
+        assert_eq!(convert::<i64>(Value::Integer(3)), Ok(3));
+        assert_eq!(convert::<f64>(Value::Float(0.5)), Ok(0.5));
+        assert_eq!(convert::<f64>(Value::Integer(2)), Ok(2.0));
+        assert_eq!(convert::<bool>(Value::Boolean(true)), Ok(true));
+        assert_eq!(
+            convert::<Rc<str>>(Value::String("a".into())),
+            Ok("a".into())
+        );
+        assert_eq!(
+            convert::<String>(Value::String("a".into())),
+            Ok("a".to_string())
+        );
+        assert_eq!(convert::<Option<i64>>(Value::None), Ok(None));
+        assert_eq!(convert::<Option<i64>>(Value::Integer(1)), Ok(Some(1)));
+        assert_eq!(convert::<Value>(Value::None), Ok(Value::None));
+    }
+
+    #[test]
+    fn from_value_failures() {
+        let cases = [
+            (convert_err::<i64>(Value::Float(1.0)), "Integer", "Float"),
+            (convert_err::<f64>(Value::Boolean(true)), "Float", "Boolean"),
+            (convert_err::<bool>(Value::Integer(1)), "Boolean", "Integer"),
+            (convert_err::<String>(Value::None), "String", "None"),
+            (
+                convert_err::<Option<i64>>(Value::String("1".into())),
+                "Integer",
+                "String",
+            ),
+        ];
+        for (err, expected, found) in cases {
+            assert_eq!(
+                err,
+                VMError::ArgumentType {
+                    index: 0,
+                    expected,
+                    found,
+                }
+            );
+            assert_eq!(
+                err.to_string(),
+                format!("Argument 1 should be {expected}, not {found}")
+            );
+        }
+    }
+
+    #[test]
+    fn missing_argument_reports_the_arity() {
+        let args = [Value::Integer(1)];
+        let err = argument::<i64>(&args, 1, 3).unwrap_err();
+        assert_eq!(
+            err,
+            VMError::ArgumentCount {
+                expected: 3,
+                found: 1,
+            }
+        );
+        assert_eq!(err.to_string(), "Expected 3 arguments but got 1");
+    }
+
+    #[test]
+    fn into_value() {
+        assert_eq!(3i64.into_value(), Value::Integer(3));
+        assert_eq!(0.5f64.into_value(), Value::Float(0.5));
+        assert_eq!(false.into_value(), Value::Boolean(false));
+        assert_eq!(Rc::<str>::from("a").into_value(), Value::String("a".into()));
+        assert_eq!("a".to_string().into_value(), Value::String("a".into()));
+        assert_eq!("a".into_value(), Value::String("a".into()));
+        assert_eq!(Some(1i64).into_value(), Value::Integer(1));
+        assert_eq!(None::<i64>.into_value(), Value::None);
+        assert_eq!(().into_value(), Value::None);
+        assert_eq!(Value::None.into_value(), Value::None);
+    }
+
+    #[test]
+    fn into_return() {
+        assert_eq!(1i64.into_return(), Ok(Value::Integer(1)));
+        assert_eq!(Ok::<_, String>(1i64).into_return(), Ok(Value::Integer(1)));
+        let err = Err::<i64, _>("bad input".to_string())
+            .into_return()
+            .unwrap_err();
+        assert_eq!(err, VMError::Custom("bad input".into()));
+        assert_eq!(err.to_string(), "bad input");
+    }
+}
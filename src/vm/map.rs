@@ -0,0 +1,198 @@
+//! The table behind [`Value::Map`], which keeps its entries in insertion
+//! order so iterating and printing a map is reproducible.
+use crate::vm::value::write_nested;
+use crate::vm::{VMError, VMResult, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// The values that can be used as map keys. Floats and collections can't,
+/// since they have no stable notion of equality to hash by.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Integer(i64),
+    Boolean(bool),
+    String(Rc<str>),
+}
+
+impl TryFrom<&Value> for Key {
+    type Error = VMError;
+
+    fn try_from(value: &Value) -> VMResult<Self> {
+        Ok(match value {
+            Value::Integer(v) => Key::Integer(*v),
+            Value::Boolean(v) => Key::Boolean(*v),
+            Value::String(v) => Key::String(v.clone()),
+            _ => return Err(VMError::UnhashableKey(value.type_name())),
+        })
+    }
+}
+
+impl From<&Key> for Value {
+    fn from(key: &Key) -> Self {
+        match key {
+            Key::Integer(v) => Value::Integer(*v),
+            Key::Boolean(v) => Value::Boolean(*v),
+            Key::String(v) => Value::String(v.clone()),
+        }
+    }
+}
+
+thread_local! {
+    /// The maps being displayed, outermost first. A cycle of imports can
+    /// leave a module's table holding itself, which is displayed as `{...}`
+    /// the second time round.
+    static DISPLAYING: RefCell<Vec<*const Map>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Default, Clone)]
+pub struct Map {
+    entries: Vec<(Key, Value)>,
+    /// Indices into `entries` by key.
+    index: HashMap<Key, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Sets the value at `key`. A key that's already present keeps its
+    /// place in the order.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    fn write_entries(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write_nested(f, &Value::from(key))?;
+            write!(f, ": ")?;
+            write_nested(f, value)?;
+        }
+        write!(f, "}}")
+    }
+
+    /// The entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// Maps are equal when they hold the same entries, in any order.
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+            || self.len() == other.len()
+                && self
+                    .iter()
+                    .all(|(key, value)| other.get(key).is_some_and(|other| other == value))
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ptr = self as *const Map;
+        if DISPLAYING.with_borrow(|maps| maps.contains(&ptr)) {
+            return write!(f, "{{...}}");
+        }
+        DISPLAYING.with_borrow_mut(|maps| maps.push(ptr));
+        let result = self.write_entries(f);
+        DISPLAYING.with_borrow_mut(|maps| maps.pop());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::testing::{eval, run};
+
+    fn key(value: &str) -> Key {
+        Key::String(value.into())
+    }
+
+    #[test]
+    fn keys() {
+        assert!(Key::try_from(&Value::Integer(1)) == Ok(Key::Integer(1)));
+        assert!(Key::try_from(&Value::Boolean(true)) == Ok(Key::Boolean(true)));
+        assert!(Key::try_from(&Value::String("a".into())) == Ok(key("a")));
+        for value in [Value::Float(1.0), Value::None, eval("return []")] {
+            assert!(
+                Key::try_from(&value) == Err(VMError::UnhashableKey(value.type_name())),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut map = Map::new();
+        assert!(map.is_empty());
+        map.insert(key("b"), Value::Integer(1));
+        map.insert(key("a"), Value::Integer(2));
+        map.insert(key("b"), Value::Integer(3));
+        assert_eq!(map.len(), 2);
+        assert!(!map.is_empty());
+        assert_eq!(map.get(&key("b")), Some(&Value::Integer(3)));
+        assert_eq!(map.get(&key("c")), None);
+        assert_eq!(map.to_string(), "{\"b\": 3, \"a\": 2}");
+    }
+
+    #[test]
+    fn literals() {
+        let source = "return {a: 1, \"b c\": 2, 3: True, False: None}";
+        assert_eq!(
+            eval(source).to_string(),
+            "{\"a\": 1, \"b c\": 2, 3: true, false: None}"
+        );
+        // A repeated key keeps its first place and its last value.
+        assert_eq!(
+            eval("return {a: 1, b: 2, a: 3}").to_string(),
+            "{\"a\": 3, \"b\": 2}"
+        );
+    }
+
+    #[test]
+    fn lookup() {
+        let source = "let m = {a: 1, 2: 3}\nreturn [m.a, m[\"a\"], m[2]]";
+        assert_eq!(eval(source).to_string(), "[1, 1, 3]");
+        assert_eq!(
+            run("return {}[\"x\"]"),
+            Err(VMError::MissingKey("String(\"x\")".to_string()))
+        );
+        assert_eq!(run("return {[1]: 2}"), Err(VMError::UnhashableKey("List")));
+    }
+
+    #[test]
+    fn equality_ignores_order() {
+        assert_eq!(
+            eval("return {a: 1, b: 2} == {b: 2, a: 1}"),
+            Value::Boolean(true)
+        );
+        assert_eq!(eval("return {a: 1} == {a: 1, b: 2}"), Value::Boolean(false));
+        assert_eq!(eval("return {a: 1} == {a: 2}"), Value::Boolean(false));
+    }
+}
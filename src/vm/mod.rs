@@ -1,17 +1,170 @@
 mod bytecode;
 mod compiler;
+pub mod convert;
+mod map;
+mod options;
+mod profile;
 mod value;
 
-use crate::report::{Maybe, ReportKind, ReportLevel};
-pub use crate::vm::bytecode::{Chunk, OpCode};
+use crate::args::Trace;
+use crate::ast::symbol::Symbol;
+use crate::cancel::CancelToken;
+use crate::eoutln;
+use crate::report::{ReportBuilder, ReportKind, ReportLevel};
+pub use crate::vm::bytecode::{Chunk, Cursor, OpCode, Program};
 pub use crate::vm::compiler::Compiler;
+pub use crate::vm::map::{Key, Map};
+pub use crate::vm::options::VmOptions;
+pub use crate::vm::profile::Profile;
 pub use crate::vm::value::Value;
+use name_variant::NamedVariant;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 
-struct VMError(String);
+pub type VMResult<T> = Result<T, VMError>;
+
+#[derive(NamedVariant, Debug, Clone, PartialEq)]
+pub enum VMError {
+    /// `op` is the verb used in the message, e.g. "add" or "negate".
+    /// Unary operations have no `rhs`.
+    TypeError {
+        op: &'static str,
+        lhs: &'static str,
+        rhs: Option<&'static str>,
+    },
+    DivisionByZero {
+        op: &'static str,
+        lhs: String,
+        rhs: String,
+    },
+    /// `op` is the operator symbol, e.g. "//".
+    IntegerOverflow {
+        op: &'static str,
+        lhs: String,
+        rhs: String,
+    },
+    FloatError {
+        lhs: f64,
+        rhs: f64,
+    },
+    /// A shift by a negative amount, or by at least the width of an
+    /// integer.
+    InvalidShift(i64),
+    UndefinedVariable(String),
+    Cancelled,
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+    },
+    /// `index` counts from zero, but is displayed counting from one.
+    ArgumentType {
+        index: usize,
+        expected: &'static str,
+        found: &'static str,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+    },
+    /// The key, in its debug form so its type shows.
+    MissingKey(String),
+    /// The type name of a value used as a map key that can't be one.
+    UnhashableKey(&'static str),
+    /// A name read from the table of a module that's part of an import
+    /// cycle and hasn't finished running, as the name and the cycle.
+    NotYetDefined {
+        name: String,
+        cycle: String,
+    },
+    /// A list destructured into a different number of elements than it has.
+    /// With `rest`, it only had to have at least `expected`.
+    DestructureLength {
+        expected: usize,
+        found: usize,
+        rest: bool,
+    },
+    /// A range whose start is after its end.
+    ReversedRange {
+        start: i64,
+        end: i64,
+    },
+    StackOverflow(usize),
+    /// The length in bytes of a string that would be created, and the limit
+    /// it's over.
+    StringTooLong {
+        length: usize,
+        limit: usize,
+    },
+    OutOfFuel(usize),
+    Custom(String),
+}
+
+impl Display for VMError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VMError::TypeError {
+                op,
+                lhs,
+                rhs: Some(rhs),
+            } => write!(f, "Cannot {op} {lhs} with {rhs}"),
+            VMError::TypeError { op, lhs, rhs: None } => write!(f, "Cannot {op} {lhs}"),
+            VMError::DivisionByZero { op, lhs, rhs } => write!(f, "Cannot {op} {lhs} by {rhs}"),
+            VMError::IntegerOverflow { op, lhs, rhs } => write!(f, "{lhs} {op} {rhs} overflows"),
+            VMError::FloatError { lhs, rhs } => write!(f, "Cannot compare {lhs} with {rhs}"),
+            VMError::InvalidShift(amount) => {
+                write!(f, "Cannot shift by {amount}, it must be from 0 to 63")
+            }
+            VMError::UndefinedVariable(name) => write!(f, "{name:?} is not defined"),
+            VMError::Cancelled => write!(f, "Execution was cancelled"),
+            VMError::ArgumentCount { expected, found } => {
+                write!(f, "Expected {expected} arguments but got {found}")
+            }
+            VMError::ArgumentType {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Argument {} should be {expected}, not {found}",
+                index + 1
+            ),
+            VMError::IndexOutOfBounds { index, length } => {
+                write!(f, "Index {index} is out of bounds for length {length}")
+            }
+            VMError::MissingKey(key) => write!(f, "The map has no key {key}"),
+            VMError::UnhashableKey(ty) => write!(f, "{ty} can't be used as a map key"),
+            VMError::NotYetDefined { name, cycle } => write!(
+                f,
+                "{name:?} isn't defined yet, since the import cycle {cycle} is still loading"
+            ),
+            VMError::DestructureLength {
+                expected,
+                found,
+                rest,
+            } => write!(
+                f,
+                "Expected {}{expected} elements to destructure but got {found}",
+                if *rest { "at least " } else { "" }
+            ),
+            VMError::ReversedRange { start, end } => write!(
+                f,
+                "The range from {start} to {end} counts down, but ranges can only count up"
+            ),
+            VMError::StackOverflow(limit) => write!(f, "Stack grew past {limit} values"),
+            VMError::StringTooLong { length, limit } => {
+                write!(f, "A string of {length} bytes is over the limit of {limit}")
+            }
+            VMError::OutOfFuel(limit) => write!(f, "Exceeded {limit} instructions"),
+            VMError::Custom(msg) => write!(f, "{msg}"),
+        }
+    }
+}
 
 impl ReportKind for VMError {
     fn title(&self) -> String {
-        format!("VM Error: {}", self.0)
+        format!("{}: {}", self.variant_name(), self)
     }
 
     fn level(&self) -> ReportLevel {
@@ -19,26 +172,115 @@ impl ReportKind for VMError {
     }
 }
 
-pub struct VM<'chunk> {
-    chunk: &'chunk mut Chunk,
-    ip: usize,
+impl From<VMError> for Box<ReportBuilder> {
+    fn from(value: VMError) -> Self {
+        value.make().into()
+    }
+}
+
+pub struct VM<'program> {
+    program: &'program Program,
+    cursor: Cursor<'program>,
     stack: Vec<Value>,
+    /// The stack slot of the running chunk's first local.
+    base: usize,
+    /// The globals of the running module. Those of the main script are kept
+    /// across runs, so a program loaded later sees the globals defined by
+    /// earlier ones.
+    globals: Rc<RefCell<Map>>,
+    /// The table of each module run so far, by the index of its chunk.
+    modules: HashMap<u16, Rc<RefCell<Map>>>,
+    /// The chunk of each module that's running, outermost first.
+    loading: Vec<u16>,
+    executed: usize,
+    options: VmOptions,
+    /// What's been executed, when profiling is on.
+    profile: Option<Profile>,
+    cancel: CancelToken,
 }
 
-impl<'c> VM<'c> {
-    pub fn new(chunk: &'c mut Chunk) -> Self {
+impl<'p> VM<'p> {
+    /// How many instructions run between checks of the cancel token.
+    const CANCEL_INTERVAL: usize = 1024;
+
+    pub fn new(program: &'p Program) -> Self {
+        let globals = Rc::new(RefCell::new(Map::new()));
         Self {
-            chunk,
-            ip: 0,
+            program,
+            cursor: program.main().cursor(),
             stack: Vec::new(),
+            base: 0,
+            modules: HashMap::from([(Program::MAIN as u16, globals.clone())]),
+            globals,
+            loading: vec![Program::MAIN as u16],
+            executed: 0,
+            options: VmOptions::default(),
+            profile: None,
+            cancel: CancelToken::new(),
         }
     }
 
-    pub fn run(&mut self) -> Maybe<Value> {
-        while self.ip < self.chunk.source.len() {
-            let op = self.chunk.read_op(&mut self.ip);
-            if crate::ARGS.trace_execution() {
-                self.chunk.disassemble_op(op, &mut self.ip.clone())
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn with_options(mut self, options: VmOptions) -> Self {
+        self.profile = options.profile.then(Profile::new);
+        self.options = options;
+        self
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Points the VM at the start of `program`, for the next [`VM::run`].
+    /// Globals and the instruction count carry over, but modules are run
+    /// again when `program` imports them.
+    pub fn load(&mut self, program: &'p Program) {
+        self.program = program;
+        self.cursor = program.main().cursor();
+        self.stack.clear();
+        self.modules.clear();
+        self.modules
+            .insert(Program::MAIN as u16, self.globals.clone());
+    }
+
+    /// The names of the main script's globals, to give
+    /// [`Compiler::with_globals`] for the next program to [`VM::load`].
+    pub fn global_names(&self) -> Vec<Symbol> {
+        self.modules[&(Program::MAIN as u16)]
+            .borrow()
+            .iter()
+            .filter_map(|(key, _)| match key {
+                Key::String(name) => Some(Symbol::intern(name)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn run(&mut self) -> VMResult<Value> {
+        while !self.cursor.at_end() {
+            if Some(self.executed) == self.options.max_instructions {
+                return Err(VMError::OutOfFuel(self.executed));
+            }
+            if self.executed.is_multiple_of(Self::CANCEL_INTERVAL) && self.cancel.is_cancelled() {
+                return Err(VMError::Cancelled);
+            }
+            self.executed += 1;
+            if self.options.trace == Some(Trace::Statements) {
+                if let Some(span) = self.cursor.chunk().statement_at(self.cursor.offset()) {
+                    eoutln!("{} | {}", span, span.excerpt());
+                }
+            }
+            if self.options.trace == Some(Trace::Instructions) {
+                Chunk::disassemble_instruction(&mut self.cursor.clone())
+            }
+            let offset = self.cursor.offset();
+            let op = self.cursor.read_op();
+            if let Some(profile) = &mut self.profile {
+                profile.record(op, self.cursor.chunk().statement_containing(offset));
             }
             match op {
                 OpCode::Return => {
@@ -50,11 +292,81 @@ impl<'c> VM<'c> {
         Ok(Value::None)
     }
 
-    pub fn run_op(&mut self, op: OpCode) -> Maybe<()> {
+    /// Prints the [`Profile`] collected under `--profile` to stderr.
+    pub fn print_profile(&self) {
+        if let Some(profile) = &self.profile {
+            eoutln!("{profile}");
+        }
+    }
+
+    fn push(&mut self, value: Value) -> VMResult<()> {
+        let limit = self.options.stack_size;
+        if self.stack.len() >= limit {
+            return Err(VMError::StackOverflow(limit));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Reads the operand of a global instruction, the string constant
+    /// naming the global.
+    fn read_global_name(&mut self) -> Rc<str> {
+        match self.cursor.read_const() {
+            Value::String(name) => name.clone(),
+            value => panic!("Global name should be a string, not {value:?}"),
+        }
+    }
+
+    /// Runs the module compiled into `chunk`, with its globals going into a
+    /// new table that's returned. The table is cached before the module
+    /// runs, so it isn't run again by a later import.
+    fn run_module(&mut self, chunk: u16) -> VMResult<Rc<RefCell<Map>>> {
+        let module = Rc::new(RefCell::new(Map::new()));
+        self.modules.insert(chunk, module.clone());
+        let cursor = self.program.chunks[chunk as usize].cursor();
+        let cursor = std::mem::replace(&mut self.cursor, cursor);
+        let globals = std::mem::replace(&mut self.globals, module.clone());
+        let base = std::mem::replace(&mut self.base, self.stack.len());
+        self.loading.push(chunk);
+        self.run()?;
+        self.loading.pop();
+        self.stack.truncate(self.base);
+        self.cursor = cursor;
+        self.globals = globals;
+        self.base = base;
+        Ok(module)
+    }
+
+    /// Replaces a missing key error from indexing `target` with `index`
+    /// by one naming the import cycle, if `target` is the table of a module
+    /// that's still running.
+    fn explain_missing_key(&self, err: VMError, target: &Value, index: &Value) -> VMError {
+        let (VMError::MissingKey(_), Value::Map(table)) = (&err, target) else {
+            return err;
+        };
+        let Some(start) = self
+            .loading
+            .iter()
+            .position(|chunk| Rc::ptr_eq(&self.modules[chunk], table))
+        else {
+            return err;
+        };
+        let mut names: Vec<&str> = self.loading[start..]
+            .iter()
+            .map(|chunk| self.program.chunks[*chunk as usize].name.as_str())
+            .collect();
+        names.push(names[0]);
+        VMError::NotYetDefined {
+            name: index.to_string(),
+            cycle: names.join(" -> "),
+        }
+    }
+
+    pub fn run_op(&mut self, op: OpCode) -> VMResult<()> {
         macro_rules! unary {
             ($op:path) => {{
                 let val = self.stack.pop().unwrap();
-                self.stack.push($op(&val)?)
+                self.push($op(&val)?)?
             }};
         }
 
@@ -62,27 +374,460 @@ impl<'c> VM<'c> {
             ($op:path) => {{
                 let rhs = self.stack.pop().unwrap();
                 let lhs = self.stack.pop().unwrap();
-                self.stack.push($op(&lhs, &rhs)?);
+                self.push($op(&lhs, &rhs)?)?;
             }};
         }
 
         match op {
             OpCode::Const => {
-                let val = self.chunk.read_const(&mut self.ip);
-                self.stack.push(val);
+                let val = self.cursor.read_const().clone();
+                self.push(val)?;
+            }
+            OpCode::None => self.push(Value::None)?,
+            OpCode::Add => {
+                let rhs = self.stack.pop().unwrap();
+                let lhs = self.stack.pop().unwrap();
+                self.push(lhs.add(&rhs, self.options.max_string_length)?)?;
             }
-            OpCode::Add => binary!(Value::add),
             OpCode::Sub => binary!(Value::sub),
-            OpCode::Mul => binary!(Value::mul),
+            OpCode::Mul => {
+                let rhs = self.stack.pop().unwrap();
+                let lhs = self.stack.pop().unwrap();
+                self.push(lhs.mul(&rhs, self.options.max_string_length)?)?;
+            }
             OpCode::Div => binary!(Value::div),
+            OpCode::FloorDiv => binary!(Value::floor_div),
             OpCode::Less => binary!(Value::lt),
-            OpCode::Greater => binary!(Value::gt),
+            OpCode::LessEqual => binary!(Value::le),
+            OpCode::Swap => {
+                let len = self.stack.len();
+                self.stack.swap(len - 1, len - 2);
+            }
             OpCode::Equal => binary!(Value::equals),
-            OpCode::And => binary!(Value::and),
-            OpCode::Or => binary!(Value::or),
             OpCode::Not => unary!(Value::not),
+            OpCode::Negate => unary!(Value::negate),
+            OpCode::BitAnd => binary!(Value::bit_and),
+            OpCode::BitOr => binary!(Value::bit_or),
+            OpCode::BitXor => binary!(Value::bit_xor),
+            OpCode::BitNot => unary!(Value::bit_not),
+            OpCode::Shl => binary!(Value::shl),
+            OpCode::Shr => binary!(Value::shr),
+            OpCode::BuildList => {
+                let count = self.cursor.read_u16() as usize;
+                let items = self.stack.split_off(self.stack.len() - count);
+                self.push(Value::List(Rc::new(RefCell::new(items))))?;
+            }
+            OpCode::BuildMap => {
+                let count = self.cursor.read_u16() as usize;
+                let mut map = Map::new();
+                let entries = self.stack.split_off(self.stack.len() - count * 2);
+                for entry in entries.chunks_exact(2) {
+                    map.insert(Key::try_from(&entry[0])?, entry[1].clone());
+                }
+                self.push(Value::Map(Rc::new(RefCell::new(map))))?;
+            }
+            OpCode::BuildRange => {
+                let inclusive = self.cursor.read_u8() != 0;
+                let end = self.stack.pop().unwrap();
+                let start = self.stack.pop().unwrap();
+                self.push(Value::range(&start, &end, inclusive)?)?;
+            }
+            OpCode::RangeBounds => {
+                let (start, end) = self.stack.pop().unwrap().range_loop_bounds()?;
+                self.push(start)?;
+                self.push(end)?;
+            }
+            OpCode::Contains => {
+                let container = self.stack.pop().unwrap();
+                let value = self.stack.pop().unwrap();
+                self.push(container.contains(&value)?)?;
+            }
+            OpCode::Unpack | OpCode::UnpackRest => {
+                let count = self.cursor.read_u8() as usize;
+                let list = self.stack.pop().unwrap();
+                for value in list.destructure(count, matches!(op, OpCode::UnpackRest))? {
+                    self.push(value)?;
+                }
+            }
+            OpCode::Index => {
+                let index = self.stack.pop().unwrap();
+                let target = self.stack.pop().unwrap();
+                let value = target
+                    .index(&index)
+                    .map_err(|err| self.explain_missing_key(err, &target, &index))?;
+                self.push(value)?;
+            }
+            OpCode::Slice => {
+                let end = self.stack.pop().unwrap();
+                let start = self.stack.pop().unwrap();
+                let target = self.stack.pop().unwrap();
+                self.push(target.slice(&start, &end)?)?;
+            }
+            OpCode::Dup => {
+                let top = self.stack.last().unwrap().clone();
+                self.push(top)?;
+            }
+            OpCode::Rot => {
+                let top = self.stack.pop().unwrap();
+                self.stack.insert(self.stack.len() - 2, top);
+            }
+            OpCode::Pop => {
+                self.stack.pop();
+            }
+            OpCode::Jump => {
+                let distance = self.cursor.read_u16();
+                self.cursor.jump(distance);
+            }
+            OpCode::JumpIfFalse => {
+                let distance = self.cursor.read_u16();
+                match self.stack.pop().unwrap() {
+                    Value::Boolean(true) => (),
+                    Value::Boolean(false) => self.cursor.jump(distance),
+                    value => {
+                        return Err(VMError::TypeError {
+                            op: "branch on",
+                            lhs: value.type_name(),
+                            rhs: None,
+                        })
+                    }
+                }
+            }
+            OpCode::JumpIfFalsy | OpCode::JumpIfTruthy => {
+                let distance = self.cursor.read_u16();
+                let cond = self.stack.pop().unwrap().is_truthy();
+                if cond == matches!(op, OpCode::JumpIfTruthy) {
+                    self.cursor.jump(distance)
+                }
+            }
+            OpCode::GetLocal => {
+                let slot = self.cursor.read_u8();
+                self.push(self.stack[self.base + slot as usize].clone())?;
+            }
+            OpCode::SetLocal => {
+                let slot = self.cursor.read_u8();
+                self.stack[self.base + slot as usize] = self.stack.last().unwrap().clone();
+            }
+            OpCode::DefineGlobal => {
+                let name = self.read_global_name();
+                let value = self.stack.pop().unwrap();
+                self.globals.borrow_mut().insert(Key::String(name), value);
+            }
+            OpCode::GetGlobal => {
+                let name = self.read_global_name();
+                let value = self
+                    .globals
+                    .borrow()
+                    .get(&Key::String(name.clone()))
+                    .cloned()
+                    .ok_or_else(|| VMError::UndefinedVariable(name.to_string()))?;
+                self.push(value)?;
+            }
+            OpCode::SetGlobal => {
+                let key = Key::String(self.read_global_name());
+                let value = self.stack.last().unwrap().clone();
+                let mut globals = self.globals.borrow_mut();
+                if globals.get(&key).is_none() {
+                    return Err(VMError::UndefinedVariable(Value::from(&key).to_string()));
+                }
+                globals.insert(key, value);
+            }
+            OpCode::Import => {
+                let chunk = self.cursor.read_u16();
+                let module = match self.modules.get(&chunk) {
+                    Some(module) => module.clone(),
+                    None => self.run_module(chunk)?,
+                };
+                self.push(Value::Map(module))?;
+            }
+            OpCode::Loop => {
+                let distance = self.cursor.read_u16();
+                self.cursor.jump_back(distance);
+            }
             OpCode::Return => unimplemented!(),
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::parse;
+    use crate::pipeline::testing::{filename, run};
+    use crate::report::ReportCollector;
+
+    /// Runs `source`, checking the variant it fails with and the title of
+    /// the report it renders as.
+    fn fails_with(source: &str, expected: VMError, title: &str) {
+        let err = run(source).expect_err("Expected a runtime error");
+        assert_eq!(err, expected);
+        assert_eq!(err.title(), title);
+    }
+
+    #[test]
+    fn type_error() {
+        fails_with(
+            "return 1 - \"a\"",
+            VMError::TypeError {
+                op: "subtract",
+                lhs: "Integer",
+                rhs: Some("String"),
+            },
+            "TypeError: Cannot subtract Integer with String",
+        );
+        fails_with(
+            "return -None",
+            VMError::TypeError {
+                op: "negate",
+                lhs: "None",
+                rhs: None,
+            },
+            "TypeError: Cannot negate None",
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        fails_with(
+            "return 1 / 0",
+            VMError::DivisionByZero {
+                op: "divide",
+                lhs: "1".into(),
+                rhs: "0".into(),
+            },
+            "DivisionByZero: Cannot divide 1 by 0",
+        );
+        fails_with(
+            "return 1 // 0",
+            VMError::DivisionByZero {
+                op: "floor divide",
+                lhs: "1".into(),
+                rhs: "0".into(),
+            },
+            "DivisionByZero: Cannot floor divide 1 by 0",
+        );
+    }
+
+    #[test]
+    fn integer_overflow() {
+        fails_with(
+            "return 9223372036854775807 + 1",
+            VMError::IntegerOverflow {
+                op: "+",
+                lhs: "9223372036854775807".into(),
+                rhs: "1".into(),
+            },
+            "IntegerOverflow: 9223372036854775807 + 1 overflows",
+        );
+    }
+
+    #[test]
+    fn float_error() {
+        let err = run("return 0.0 / 0.0 < 1.0").expect_err("Expected a runtime error");
+        assert!(matches!(err, VMError::FloatError { lhs, rhs: 1.0 } if lhs.is_nan()));
+        assert_eq!(err.title(), "FloatError: Cannot compare NaN with 1");
+    }
+
+    #[test]
+    fn invalid_shift() {
+        fails_with(
+            "return 1 << 64",
+            VMError::InvalidShift(64),
+            "InvalidShift: Cannot shift by 64, it must be from 0 to 63",
+        );
+    }
+
+    #[test]
+    fn index_out_of_bounds() {
+        fails_with(
+            "return [1][5]",
+            VMError::IndexOutOfBounds {
+                index: 5,
+                length: 1,
+            },
+            "IndexOutOfBounds: Index 5 is out of bounds for length 1",
+        );
+    }
+
+    #[test]
+    fn missing_key() {
+        fails_with(
+            "return {a: 1}[\"b\"]",
+            VMError::MissingKey("String(\"b\")".into()),
+            "MissingKey: The map has no key String(\"b\")",
+        );
+    }
+
+    #[test]
+    fn unhashable_key() {
+        fails_with(
+            "return {[1]: 2}",
+            VMError::UnhashableKey("List"),
+            "UnhashableKey: List can't be used as a map key",
+        );
+    }
+
+    #[test]
+    fn reversed_range() {
+        fails_with(
+            "let a = 3\nreturn a..1",
+            VMError::ReversedRange { start: 3, end: 1 },
+            "ReversedRange: The range from 3 to 1 counts down, but ranges can only count up",
+        );
+    }
+
+    #[test]
+    fn destructure_length() {
+        fails_with(
+            "let a, b = [1]",
+            VMError::DestructureLength {
+                expected: 2,
+                found: 1,
+                rest: false,
+            },
+            "DestructureLength: Expected 2 elements to destructure but got 1",
+        );
+    }
+
+    /// The variants scripts can't reach yet, or only under limits the
+    /// tests don't set.
+    #[test]
+    fn titles() {
+        let cases = [
+            (
+                VMError::UndefinedVariable("x".into()),
+                "UndefinedVariable: \"x\" is not defined",
+            ),
+            (VMError::Cancelled, "Cancelled: Execution was cancelled"),
+            (
+                VMError::ArgumentCount {
+                    expected: 2,
+                    found: 1,
+                },
+                "ArgumentCount: Expected 2 arguments but got 1",
+            ),
+            (
+                VMError::ArgumentType {
+                    index: 0,
+                    expected: "Integer",
+                    found: "String",
+                },
+                "ArgumentType: Argument 1 should be Integer, not String",
+            ),
+            (
+                VMError::NotYetDefined {
+                    name: "x".into(),
+                    cycle: "a.ml -> b.ml -> a.ml".into(),
+                },
+                "NotYetDefined: \"x\" isn't defined yet, since the import cycle a.ml -> b.ml -> a.ml is still loading",
+            ),
+            (
+                VMError::StackOverflow(4096),
+                "StackOverflow: Stack grew past 4096 values",
+            ),
+            (
+                VMError::StringTooLong {
+                    length: 10,
+                    limit: 5,
+                },
+                "StringTooLong: A string of 10 bytes is over the limit of 5",
+            ),
+            (
+                VMError::OutOfFuel(100),
+                "OutOfFuel: Exceeded 100 instructions",
+            ),
+            (VMError::Custom("oops".into()), "Custom: oops"),
+        ];
+        for (err, title) in cases {
+            assert_eq!(err.title(), title);
+        }
+    }
+
+    /// Runs `chunk` on its own, without the parser and compiler.
+    fn run_chunk(chunk: Chunk) -> VMResult<Value> {
+        let mut program = Program::new();
+        program.chunks.push(chunk);
+        VM::new(&program).run()
+    }
+
+    #[test]
+    fn hand_assembled_arithmetic() {
+        let chunk = Chunk::new()
+            .with_const(Value::Integer(6))
+            .with_const(Value::Integer(4))
+            .with_op(OpCode::Sub)
+            .with_const(Value::Integer(3))
+            .with_op(OpCode::Mul)
+            .with_op(OpCode::Negate)
+            .with_op(OpCode::Return);
+        assert_eq!(run_chunk(chunk), Ok(Value::Integer(-6)));
+    }
+
+    #[test]
+    fn hand_assembled_jumps() {
+        let chunk = |condition: bool| {
+            Chunk::new()
+                .with_const(Value::Boolean(condition))
+                .with_op(OpCode::JumpIfFalse)
+                .with_u16(4)
+                .with_const(Value::Integer(1))
+                .with_op(OpCode::Return)
+                .with_const(Value::Integer(2))
+                .with_op(OpCode::Return)
+        };
+        assert_eq!(run_chunk(chunk(true)), Ok(Value::Integer(1)));
+        assert_eq!(run_chunk(chunk(false)), Ok(Value::Integer(2)));
+        let chunk = Chunk::new()
+            .with_op(OpCode::Jump)
+            .with_u16(4)
+            .with_const(Value::Integer(1))
+            .with_op(OpCode::Return)
+            .with_op(OpCode::None)
+            .with_op(OpCode::Return);
+        assert_eq!(run_chunk(chunk), Ok(Value::None));
+    }
+
+    #[test]
+    fn hand_assembled_without_return() {
+        assert_eq!(run_chunk(Chunk::from_ops(&[])), Ok(Value::None));
+        let chunk = Chunk::from_ops(&[OpCode::None, OpCode::Pop]);
+        assert_eq!(run_chunk(chunk), Ok(Value::None));
+    }
+
+    #[test]
+    #[should_panic]
+    fn stack_underflow() {
+        let _ = run_chunk(Chunk::from_ops(&[OpCode::None, OpCode::Add]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid opcode 0xff")]
+    fn bad_opcode() {
+        let _ = run_chunk(Chunk::new().with_u8(0xFF));
+    }
+
+    /// Compiles `source` with `globals` already declared.
+    fn compile_with_globals(source: &str, globals: Vec<Symbol>) -> Program {
+        let (ast, _) = parse(filename(), source.to_string(), &CancelToken::new())
+            .unwrap_or_else(|_| panic!("Failed to parse {source}"));
+        let collector = ReportCollector::new();
+        let mut compiler = Compiler::new(collector.clone()).with_globals(globals);
+        compiler.compile_program(&ast);
+        assert!(collector.take().is_empty(), "Failed to compile {source}");
+        compiler.program
+    }
+
+    #[test]
+    fn globals_are_kept_across_loads() {
+        let first = compile_with_globals("let x = 1\nlet y = 2", Vec::new());
+        let mut vm = VM::new(&first);
+        assert_eq!(vm.run(), Ok(Value::None));
+        assert_eq!(
+            vm.global_names(),
+            [Symbol::intern("x"), Symbol::intern("y")]
+        );
+        let second = compile_with_globals("x += y\nreturn x", vm.global_names());
+        vm.load(&second);
+        assert_eq!(vm.run(), Ok(Value::Integer(3)));
+    }
+}
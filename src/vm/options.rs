@@ -0,0 +1,181 @@
+//! The limits and instrumentation a program is compiled and run with, for
+//! embedders to set directly. The command line flags only fill these in.
+use crate::args::{Trace, ARGS};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmOptions {
+    /// Stops the VM with an error after this many instructions.
+    pub max_instructions: Option<usize>,
+    /// The most values the stack can hold.
+    pub stack_size: usize,
+    /// The longest string, in bytes, that concatenation or repetition can
+    /// build.
+    pub max_string_length: usize,
+    /// The directory every imported module has to be inside, once symlinks
+    /// are resolved.
+    pub import_root: Option<PathBuf>,
+    pub trace: Option<Trace>,
+    /// Whether to collect a [`Profile`](crate::vm::Profile).
+    pub profile: bool,
+}
+
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            stack_size: 1 << 16,
+            max_string_length: 1 << 30,
+            import_root: None,
+            trace: None,
+            profile: false,
+        }
+    }
+}
+
+impl VmOptions {
+    /// Limits for running an untrusted script, which can only import modules
+    /// from inside `root`.
+    pub fn sandbox(root: &Path) -> Self {
+        Self {
+            max_instructions: Some(100_000_000),
+            stack_size: 1 << 12,
+            max_string_length: 1 << 20,
+            import_root: Some(root.to_path_buf()),
+            ..Self::default()
+        }
+    }
+
+    /// The options given on the command line.
+    pub fn from_args() -> Self {
+        Self {
+            max_instructions: ARGS.max_instructions(),
+            stack_size: ARGS.stack_size(),
+            max_string_length: ARGS.max_string_length(),
+            import_root: ARGS.import_root().map(PathBuf::from),
+            trace: ARGS.trace(),
+            profile: ARGS.profile(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancelToken;
+    use crate::pipeline::testing::{filename, path_name, temp_dir, titles};
+    use crate::pipeline::{run_source, SourceError};
+    use crate::vm::{VMError, Value};
+
+    fn run_with(source: &str, options: VmOptions) -> Result<Value, SourceError> {
+        run_source(
+            filename(),
+            source.to_string(),
+            &options,
+            &CancelToken::new(),
+        )
+    }
+
+    fn fails_with(source: &str, options: VmOptions, expected: VMError) {
+        match run_with(source, options) {
+            Err(SourceError::Runtime(err)) => assert_eq!(err, expected),
+            Ok(value) => panic!("{source} returned {value}"),
+            Err(SourceError::Diagnostics(diagnostics)) => {
+                panic!("{source} failed to compile with {:?}", titles(&diagnostics))
+            }
+        }
+    }
+
+    #[test]
+    fn instruction_budget() {
+        let options = VmOptions {
+            max_instructions: Some(1000),
+            ..VmOptions::default()
+        };
+        fails_with("loop {}", options.clone(), VMError::OutOfFuel(1000));
+        assert!(run_with("return 1 + 2", options).is_ok());
+    }
+
+    #[test]
+    fn stack_limit() {
+        let options = VmOptions {
+            stack_size: 4,
+            ..VmOptions::default()
+        };
+        fails_with(
+            "return [1, 2, 3, 4, 5]",
+            options.clone(),
+            VMError::StackOverflow(4),
+        );
+        assert!(run_with("return [1, 2, 3, 4]", options).is_ok());
+    }
+
+    #[test]
+    fn string_length() {
+        let options = VmOptions {
+            max_string_length: 8,
+            ..VmOptions::default()
+        };
+        fails_with(
+            "return \"abc\" * 3",
+            options.clone(),
+            VMError::StringTooLong {
+                length: 9,
+                limit: 8,
+            },
+        );
+        fails_with(
+            "return \"abcde\" + \"abcd\"",
+            options.clone(),
+            VMError::StringTooLong {
+                length: 9,
+                limit: 8,
+            },
+        );
+        assert!(run_with("return \"abcd\" * 2", options).is_ok());
+    }
+
+    /// Compiles `main.ml` in `dir`, which imports `import`, with imports
+    /// limited to `root`.
+    fn import_titles(dir: &Path, import: &str, root: &Path) -> Vec<String> {
+        let source = format!("import {import}\nreturn 1");
+        let options = VmOptions::sandbox(root);
+        let main = path_name(dir.join("main.ml"));
+        match run_source(main, source, &options, &CancelToken::new()) {
+            Ok(_) => Vec::new(),
+            Err(SourceError::Diagnostics(diagnostics)) => {
+                titles(&diagnostics).into_iter().map(String::from).collect()
+            }
+            Err(SourceError::Runtime(err)) => panic!("Failed to run: {err}"),
+        }
+    }
+
+    #[test]
+    fn import_root() {
+        let dir = temp_dir();
+        std::fs::create_dir(dir.join("root")).unwrap();
+        std::fs::write(dir.join("outside.moon"), "let x = 1").unwrap();
+        std::fs::write(dir.join("root").join("inside.moon"), "let x = 1").unwrap();
+        let root = dir.join("root");
+        assert!(import_titles(&root, "inside", &root).is_empty());
+        assert_eq!(import_titles(&dir, "outside", &root), ["ImportOutsideRoot"]);
+        assert_eq!(
+            import_titles(&dir, "root.inside", &root),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn import_root_resolves_symlinks() {
+        let dir = temp_dir();
+        let root = dir.join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(dir.join("outside.moon"), "let x = 1").unwrap();
+        std::os::unix::fs::symlink(&dir, root.join("escape")).unwrap();
+        assert_eq!(
+            import_titles(&root, "escape.outside", &root),
+            ["ImportOutsideRoot"]
+        );
+    }
+}
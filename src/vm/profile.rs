@@ -0,0 +1,190 @@
+//! The counts `--profile` collects, for deciding which instructions and
+//! which parts of a script are worth making faster.
+use crate::ast::span::Span;
+use crate::report::UnwrapReport;
+use crate::vm::OpCode;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+pub struct Profile {
+    /// Executions per opcode, indexed by its byte.
+    ops: Box<[usize; 256]>,
+    /// Instructions executed per statement, by the span the chunk's
+    /// statement table gives it.
+    statements: HashMap<Span, usize>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    /// How many lines the hot lines table shows.
+    pub const HOT_LINES: usize = 10;
+
+    pub fn new() -> Self {
+        Self {
+            ops: Box::new([0; 256]),
+            statements: HashMap::new(),
+        }
+    }
+
+    /// Counts an execution of `op`, which is part of `statement` if it's
+    /// known.
+    pub fn record(&mut self, op: OpCode, statement: Option<Span>) {
+        self.ops[op as usize] += 1;
+        if let Some(statement) = statement {
+            *self.statements.entry(statement).or_default() += 1;
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.ops.iter().sum()
+    }
+
+    /// Each opcode executed and how many times, most executed first and
+    /// then by name.
+    pub fn op_counts(&self) -> Vec<(OpCode, usize)> {
+        let mut counts: Vec<(OpCode, usize)> = self
+            .ops
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(op, count)| (OpCode::try_from(op as u8).unwrap(), *count))
+            .collect();
+        counts.sort_by(|(a_op, a), (b_op, b)| b.cmp(a).then_with(|| a_op.name().cmp(b_op.name())));
+        counts
+    }
+
+    /// The source lines that executed the most instructions, as the file,
+    /// the line number and the count, most executed first and then in
+    /// source order. Statements sharing a line are counted together.
+    pub fn hot_lines(&self) -> Vec<(&'static str, usize, usize)> {
+        let mut lines: HashMap<(&'static str, usize), usize> = HashMap::new();
+        for (span, count) in &self.statements {
+            *lines
+                .entry((span.filename, span.start_location().line()))
+                .or_default() += count;
+        }
+        let mut lines: Vec<_> = lines
+            .into_iter()
+            .map(|((file, line), count)| (file, line, count))
+            .collect();
+        lines.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (a.0, a.1).cmp(&(b.0, b.1))));
+        lines.truncate(Self::HOT_LINES);
+        lines
+    }
+}
+
+/// Two tables, of the opcodes and of the hot lines, each with a percentage
+/// of every instruction executed.
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let total = self.total();
+        let percent = |count: usize| count as f64 * 100.0 / total as f64;
+        writeln!(f, "Profile:")?;
+        for (op, count) in self.op_counts() {
+            writeln!(f, "  {:<16}{:<12}{:.2}%", op.name(), count, percent(count))?;
+        }
+        write!(f, "Hot lines:")?;
+        for (file, line, count) in self.hot_lines() {
+            let source = crate::files::get_source(file).unwrap_report();
+            let text = source.text().lines().nth(line - 1).unwrap_or("");
+            write!(
+                f,
+                "\n  {:<16}{:<12}{:<8}| {}",
+                format!("{file}:{line}"),
+                count,
+                format!("{:.2}%", percent(count)),
+                text.trim()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cancel::CancelToken;
+    use crate::pipeline::compile_source;
+    use crate::pipeline::testing::filename;
+    use crate::vm::{VmOptions, VM};
+
+    const FIXTURE: &str = "let i = 0
+while i < 3 {
+    i = i + 1
+}
+return i";
+
+    fn profile(filename: &'static str) -> String {
+        let Ok((program, _)) = compile_source(
+            filename,
+            FIXTURE.to_string(),
+            &VmOptions::default(),
+            &CancelToken::new(),
+        ) else {
+            panic!("Failed to compile");
+        };
+        let options = VmOptions {
+            profile: true,
+            ..VmOptions::default()
+        };
+        let mut vm = VM::new(&program).with_options(options);
+        vm.run().unwrap();
+        vm.profile().unwrap().to_string()
+    }
+
+    #[test]
+    fn counts_every_opcode_executed() {
+        let profile = profile(filename());
+        let ops = [
+            "Const",
+            "DefineGlobal",
+            "GetGlobal",
+            "Less",
+            "JumpIfFalse",
+            "Add",
+            "SetGlobal",
+            "Pop",
+            "Loop",
+            "Return",
+        ];
+        for op in ops {
+            assert!(
+                profile
+                    .lines()
+                    .any(|line| line.trim_start().starts_with(op)),
+                "{op} is missing from\n{profile}"
+            );
+        }
+        assert!(profile.contains("\n  Add             3           7.89%\n"));
+    }
+
+    #[test]
+    fn hot_lines() {
+        let filename = filename();
+        let profile = profile(filename);
+        let (_, hot_lines) = profile.split_once("Hot lines:\n").unwrap();
+        let expected = [
+            format!(
+                "  {:<16}18          47.37%  | i = i + 1",
+                format!("{filename}:3")
+            ),
+            format!(
+                "  {:<16}16          42.11%  | while i < 3 {{",
+                format!("{filename}:2")
+            ),
+            format!(
+                "  {:<16}2           5.26%   | let i = 0",
+                format!("{filename}:1")
+            ),
+            format!(
+                "  {:<16}2           5.26%   | return i",
+                format!("{filename}:5")
+            ),
+        ];
+        assert_eq!(hot_lines.lines().collect::<Vec<_>>(), expected);
+    }
+}
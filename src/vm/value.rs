@@ -1,44 +1,41 @@
-use crate::report::{Maybe, ReportKind, ReportLevel};
+use crate::vm::map::{Key, Map};
+use crate::vm::{VMError, VMResult};
 use name_variant::NamedVariant;
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
-
-#[derive(NamedVariant)]
-enum ValueReport {
-    TypeError(String),
-    FloatError(String),
-}
-
-impl Display for ValueReport {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.variant_name())?;
-        match self {
-            ValueReport::TypeError(msg) => write!(f, ": {}", msg),
-            ValueReport::FloatError(msg) => write!(f, ": {}", msg),
-        }
-    }
-}
-
-impl ReportKind for ValueReport {
-    fn title(&self) -> String {
-        format!("{}", self)
-    }
-
-    fn level(&self) -> ReportLevel {
-        ReportLevel::Error
-    }
-}
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 #[repr(u8)]
 #[derive(NamedVariant, PartialEq, Clone)]
 pub enum Value {
-    Integer(isize),
+    Integer(i64),
     Float(f64),
     Boolean(bool),
-    String(String),
+    /// Shared with the [`Program`](crate::vm::Program) string table when
+    /// loaded from a constant, so identical literals are stored once.
+    String(Rc<str>),
+    /// Shared, so copies of a list see each other's changes.
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    /// The integers from `start` up to `end`, including `end` only if the
+    /// range is inclusive. `start` is never after `end`.
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
     None,
 }
 
+impl Value {
+    /// The name of the value's type, as runtime errors show it.
+    pub fn type_name(&self) -> &'static str {
+        self.variant_name()
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -46,11 +43,36 @@ impl Display for Value {
             Value::Float(v) => write!(f, "{}", v),
             Value::Boolean(v) => write!(f, "{}", v),
             Value::String(v) => write!(f, "{}", v),
+            Value::List(v) => {
+                write!(f, "[")?;
+                for (i, item) in v.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_nested(f, item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(v) => write!(f, "{}", v.borrow()),
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{start}..{}{end}", if *inclusive { "=" } else { "" }),
             Value::None => write!(f, "None"),
         }
     }
 }
 
+/// Writes a value held inside a list or map, quoting strings so `["1"]`
+/// and `[1]` look different.
+pub(super) fn write_nested(f: &mut Formatter<'_>, value: &Value) -> std::fmt::Result {
+    match value {
+        Value::String(value) => write!(f, "{:?}", value),
+        value => write!(f, "{}", value),
+    }
+}
+
 impl std::fmt::Debug for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}(", self.variant_name())?;
@@ -59,162 +81,705 @@ impl std::fmt::Debug for Value {
             Value::Float(v) => write!(f, "{}", v)?,
             Value::Boolean(v) => write!(f, "{}", v)?,
             Value::String(v) => write!(f, "{:?}", v)?,
+            Value::List(v) => write!(f, "{:?}", v.borrow())?,
+            Value::Map(v) => write!(f, "{}", v.borrow())?,
+            Value::Range { .. } => write!(f, "{}", self)?,
             Value::None => write!(f, "None")?,
         }
         write!(f, ")")
     }
 }
 
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Integer(v) => v.hash(state),
+            // 0.0 and -0.0 compare equal, so they have to hash the same.
+            Value::Float(v) => (if *v == 0.0 { 0.0 } else { *v }).to_bits().hash(state),
+            Value::Boolean(v) => v.hash(state),
+            Value::String(v) => v.hash(state),
+            Value::List(v) => v.borrow().hash(state),
+            // Equal maps can list their entries in different orders.
+            Value::Map(v) => v.borrow().len().hash(state),
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => (start, end, inclusive).hash(state),
+            Value::None => (),
+        }
+    }
+}
+
+/// Checks that a string of `length` bytes is within `limit` before it's
+/// built.
+fn check_string_length(length: usize, limit: usize) -> VMResult<()> {
+    if length > limit {
+        return Err(VMError::StringTooLong { length, limit });
+    }
+    Ok(())
+}
+
+/// Repeats `string` `count` times, where a count below one gives an empty
+/// string.
+fn repeat(string: &str, count: i64, limit: usize) -> VMResult<Value> {
+    let count = usize::try_from(count).unwrap_or(0);
+    check_string_length(string.len().saturating_mul(count), limit)?;
+    Ok(Value::String(string.repeat(count).into()))
+}
+
+/// Applies a bitwise operation, which only integers support. `op` is the
+/// verb used in the error otherwise.
+fn bitwise(op: &'static str, lhs: &Value, rhs: &Value, f: fn(i64, i64) -> i64) -> VMResult<Value> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(f(*a, *b))),
+        _ => Err(VMError::TypeError {
+            op,
+            lhs: lhs.variant_name(),
+            rhs: Some(rhs.variant_name()),
+        }),
+    }
+}
+
+/// Shifts `lhs` by `rhs` bits with `f`, which only integers support. `op`
+/// is the verb used in the error otherwise.
+fn shift(
+    op: &'static str,
+    lhs: &Value,
+    rhs: &Value,
+    f: fn(i64, u32) -> Option<i64>,
+) -> VMResult<Value> {
+    match (lhs, rhs) {
+        (Value::Integer(a), Value::Integer(b)) => u32::try_from(*b)
+            .ok()
+            .and_then(|b| f(*a, b))
+            .map(Value::Integer)
+            .ok_or(VMError::InvalidShift(*b)),
+        _ => Err(VMError::TypeError {
+            op,
+            lhs: lhs.variant_name(),
+            rhs: Some(rhs.variant_name()),
+        }),
+    }
+}
+
+/// Applies an overflow-checked integer operation, reporting `op` as the
+/// operator symbol when it overflows.
+fn checked_integer(
+    op: &'static str,
+    lhs: i64,
+    rhs: i64,
+    f: fn(i64, i64) -> Option<i64>,
+) -> VMResult<Value> {
+    f(lhs, rhs)
+        .map(Value::Integer)
+        .ok_or_else(|| VMError::IntegerOverflow {
+            op,
+            lhs: lhs.to_string(),
+            rhs: rhs.to_string(),
+        })
+}
+
 impl Value {
-    pub fn add(&self, other: &Value) -> Maybe<Value> {
+    /// Makes the range from `start` to `end`, which have to be integers in
+    /// order.
+    pub fn range(start: &Value, end: &Value, inclusive: bool) -> VMResult<Value> {
+        match (start, end) {
+            (Value::Integer(start), Value::Integer(end)) if start > end => {
+                Err(VMError::ReversedRange {
+                    start: *start,
+                    end: *end,
+                })
+            }
+            (Value::Integer(start), Value::Integer(end)) => Ok(Value::Range {
+                start: *start,
+                end: *end,
+                inclusive,
+            }),
+            _ => Err(VMError::TypeError {
+                op: "make a range from",
+                lhs: start.variant_name(),
+                rhs: Some(end.variant_name()),
+            }),
+        }
+    }
+
+    /// The first integer of a range and the one after its last, which can
+    /// be past `i64::MAX` for an inclusive range.
+    fn range_bounds(&self) -> Option<(i64, i128)> {
+        match self {
+            Value::Range {
+                start,
+                end,
+                inclusive,
+            } => Some((*start, *end as i128 + *inclusive as i128)),
+            _ => None,
+        }
+    }
+
+    /// The first integer of a range and the one after its last, for a
+    /// `for` loop to count between.
+    pub fn range_loop_bounds(&self) -> VMResult<(Value, Value)> {
+        let Some((start, end)) = self.range_bounds() else {
+            return Err(VMError::TypeError {
+                op: "loop over",
+                lhs: self.variant_name(),
+                rhs: None,
+            });
+        };
+        let end = i64::try_from(end).map_err(|_| VMError::IntegerOverflow {
+            op: "+",
+            lhs: i64::MAX.to_string(),
+            rhs: 1.to_string(),
+        })?;
+        Ok((Value::Integer(start), Value::Integer(end)))
+    }
+
+    /// Adding a string to anything else converts the other operand to a
+    /// string and concatenates them, as long as the result is no longer
+    /// than `max_string_length`.
+    pub fn add(&self, other: &Value, max_string_length: usize) -> VMResult<Value> {
         Ok(match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a + b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                checked_integer("+", *a, *b, i64::checked_add)?
+            }
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 + b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a + *b as f64),
-            (Value::String(a), Value::String(b)) => Value::String(format!("{}{}", a, b)),
+            (Value::String(_), _) | (_, Value::String(_)) => {
+                let string = format!("{}{}", self, other);
+                check_string_length(string.len(), max_string_length)?;
+                Value::String(string.into())
+            }
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot add {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "add",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
             }
         })
     }
 
-    pub fn sub(&self, other: &Value) -> Maybe<Value> {
+    pub fn sub(&self, other: &Value) -> VMResult<Value> {
         Ok(match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a - b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                checked_integer("-", *a, *b, i64::checked_sub)?
+            }
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 - b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a - *b as f64),
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot subtract {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "subtract",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
             }
         })
     }
 
-    pub fn mul(&self, other: &Value) -> Maybe<Value> {
+    /// Multiplying a string by an integer repeats it, as long as the result
+    /// is no longer than `max_string_length`.
+    pub fn mul(&self, other: &Value, max_string_length: usize) -> VMResult<Value> {
         Ok(match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a * b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                checked_integer("*", *a, *b, i64::checked_mul)?
+            }
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 * b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a * *b as f64),
-            (Value::String(a), Value::Integer(b)) => Value::String(a.repeat(*b as usize)),
+            (Value::String(a), Value::Integer(b)) | (Value::Integer(b), Value::String(a)) => {
+                repeat(a, *b, max_string_length)?
+            }
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot multiply {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "multiply",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
             }
         })
     }
 
-    pub fn div(&self, other: &Value) -> Maybe<Value> {
+    /// True division, which always produces a float. Dividing two integers
+    /// by zero is an error, while float operands follow IEEE 754 and may
+    /// produce infinity or NaN.
+    pub fn div(&self, other: &Value) -> VMResult<Value> {
         Ok(match (self, other) {
+            (Value::Integer(a), Value::Integer(0)) => {
+                return Err(VMError::DivisionByZero {
+                    op: "divide",
+                    lhs: a.to_string(),
+                    rhs: 0.to_string(),
+                })
+            }
             (Value::Integer(a), Value::Integer(b)) => Value::Float(*a as f64 / *b as f64),
             (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 / b),
             (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a / *b as f64),
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot divide {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "divide",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
             }
         })
     }
 
-    pub fn cmp(&self, other: &Value) -> Maybe<Ordering> {
+    /// Floor division, rounding toward negative infinity. Two integers
+    /// produce an integer, otherwise the result is a floored float.
+    /// Division by zero is always an error.
+    pub fn floor_div(&self, other: &Value) -> VMResult<Value> {
+        let zero = match other {
+            Value::Integer(b) => *b == 0,
+            Value::Float(b) => *b == 0.0,
+            _ => false,
+        };
+        if zero {
+            return Err(VMError::DivisionByZero {
+                op: "floor divide",
+                lhs: self.to_string(),
+                rhs: other.to_string(),
+            });
+        }
+        Ok(match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                let quotient = a.checked_div(*b).ok_or_else(|| VMError::IntegerOverflow {
+                    op: "//",
+                    lhs: a.to_string(),
+                    rhs: b.to_string(),
+                })?;
+                if a % b != 0 && (*a < 0) != (*b < 0) {
+                    Value::Integer(quotient - 1)
+                } else {
+                    Value::Integer(quotient)
+                }
+            }
+            (Value::Integer(a), Value::Float(b)) => Value::Float((*a as f64 / b).floor()),
+            (Value::Float(a), Value::Float(b)) => Value::Float((a / b).floor()),
+            (Value::Float(a), Value::Integer(b)) => Value::Float((a / *b as f64).floor()),
+            _ => {
+                return Err(VMError::TypeError {
+                    op: "floor divide",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
+            }
+        })
+    }
+
+    pub fn compare(&self, other: &Value) -> VMResult<Ordering> {
         Ok(match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
             (Value::Float(a), Value::Float(b)) => {
-                return a.partial_cmp(b).ok_or_else(|| {
-                    ValueReport::FloatError(format!("Cannot compare {} with {}", a, b))
-                        .make()
-                        .into()
-                })
+                return a
+                    .partial_cmp(b)
+                    .ok_or(VMError::FloatError { lhs: *a, rhs: *b })
             }
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot compare {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "compare",
+                    lhs: self.variant_name(),
+                    rhs: Some(other.variant_name()),
+                })
             }
         })
     }
 
-    pub fn equals(&self, other: &Value) -> Maybe<Value> {
+    /// Values of different types are never equal, so `None` only equals
+    /// itself and `1 == 1.0` is false.
+    pub fn equals(&self, other: &Value) -> VMResult<Value> {
         Ok(Value::Boolean(self.eq(other)))
     }
 
-    pub fn gt(&self, other: &Value) -> Maybe<Value> {
-        Ok(Value::Boolean(self.cmp(other)?.eq(&Ordering::Greater)))
+    pub fn lt(&self, other: &Value) -> VMResult<Value> {
+        Ok(Value::Boolean(self.compare(other)?.eq(&Ordering::Less)))
     }
 
-    pub fn lt(&self, other: &Value) -> Maybe<Value> {
-        Ok(Value::Boolean(self.cmp(other)?.eq(&Ordering::Less)))
+    pub fn le(&self, other: &Value) -> VMResult<Value> {
+        Ok(Value::Boolean(self.compare(other)?.ne(&Ordering::Greater)))
     }
 
-    pub fn and(&self, other: &Value) -> Maybe<Value> {
-        Ok(match (self, other) {
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(*a && *b),
+    pub fn bit_and(&self, other: &Value) -> VMResult<Value> {
+        bitwise("bitwise and", self, other, |a, b| a & b)
+    }
+
+    pub fn bit_or(&self, other: &Value) -> VMResult<Value> {
+        bitwise("bitwise or", self, other, |a, b| a | b)
+    }
+
+    pub fn bit_xor(&self, other: &Value) -> VMResult<Value> {
+        bitwise("bitwise xor", self, other, |a, b| a ^ b)
+    }
+
+    /// Bits shifted past the top are dropped, so this may change the sign.
+    pub fn shl(&self, other: &Value) -> VMResult<Value> {
+        shift("shift", self, other, i64::checked_shl)
+    }
+
+    pub fn shr(&self, other: &Value) -> VMResult<Value> {
+        shift("shift", self, other, i64::checked_shr)
+    }
+
+    pub fn bit_not(&self) -> VMResult<Value> {
+        Ok(match self {
+            Value::Integer(value) => Value::Integer(!*value),
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot and {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "bitwise negate",
+                    lhs: self.variant_name(),
+                    rhs: None,
+                })
             }
         })
     }
 
-    pub fn or(&self, other: &Value) -> Maybe<Value> {
-        Ok(match (self, other) {
-            (Value::Boolean(a), Value::Boolean(b)) => Value::Boolean(*a || *b),
+    pub fn negate(&self) -> VMResult<Value> {
+        Ok(match self {
+            Value::Integer(value) => checked_integer("-", 0, *value, i64::checked_sub)?,
+            Value::Float(value) => Value::Float(-value),
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot or {} with {}",
-                    self.variant_name(),
-                    other.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "negate",
+                    lhs: self.variant_name(),
+                    rhs: None,
+                })
             }
         })
     }
 
-    pub fn not(&self) -> Maybe<Value> {
-        Ok(match self {
-            Value::Boolean(value) => Value::Boolean(!*value),
+    /// The values a destructuring `let` binds to `count` names: the
+    /// elements of a list, then with `rest`, a list of any after those.
+    pub fn destructure(&self, count: usize, rest: bool) -> VMResult<Vec<Value>> {
+        let Value::List(list) = self else {
+            return Err(VMError::TypeError {
+                op: "destructure",
+                lhs: self.variant_name(),
+                rhs: None,
+            });
+        };
+        let list = list.borrow();
+        if list.len() < count || !rest && list.len() > count {
+            return Err(VMError::DestructureLength {
+                expected: count,
+                found: list.len(),
+                rest,
+            });
+        }
+        let mut values = list[..count].to_vec();
+        if rest {
+            values.push(Value::List(Rc::new(RefCell::new(list[count..].to_vec()))));
+        }
+        Ok(values)
+    }
+
+    /// Whether `value` is an element of a list or range, a key of a map, or
+    /// a substring of a string. Membership goes by [`Value::equals`], so
+    /// `1.0 in [1]` is false, and a value that can't be a key is in no map.
+    pub fn contains(&self, value: &Value) -> VMResult<Value> {
+        Ok(Value::Boolean(match (self, value) {
+            (Value::List(list), value) => list.borrow().contains(value),
+            (Value::Map(map), key) => {
+                Key::try_from(key).is_ok_and(|key| map.borrow().get(&key).is_some())
+            }
+            (Value::String(string), Value::String(substring)) => string.contains(&**substring),
+            (Value::Range { .. }, Value::Integer(i)) => {
+                let (start, end) = self.range_bounds().unwrap();
+                start <= *i && (*i as i128) < end
+            }
+            (Value::Range { .. }, _) => false,
+            _ => {
+                return Err(VMError::TypeError {
+                    op: "search",
+                    lhs: self.variant_name(),
+                    rhs: Some(value.variant_name()),
+                })
+            }
+        }))
+    }
+
+    /// Indexes a list by an integer counting from zero, or looks up a key
+    /// in a map.
+    pub fn index(&self, index: &Value) -> VMResult<Value> {
+        match (self, index) {
+            (Value::Map(map), key) => map
+                .borrow()
+                .get(&Key::try_from(key)?)
+                .cloned()
+                .ok_or_else(|| VMError::MissingKey(format!("{:?}", key))),
+            (Value::Range { start, end, .. }, Value::String(member)) => match &**member {
+                "start" => Ok(Value::Integer(*start)),
+                "end" => Ok(Value::Integer(*end)),
+                "len" => {
+                    let (start, end) = self.range_bounds().unwrap();
+                    Ok(Value::Integer(
+                        (end - start as i128).try_into().unwrap_or(i64::MAX),
+                    ))
+                }
+                _ => Err(VMError::MissingKey(format!("{:?}", index))),
+            },
+            (Value::Range { .. }, Value::Integer(i)) => {
+                let (start, end) = self.range_bounds().unwrap();
+                let length = end - start as i128;
+                match start as i128 + *i as i128 {
+                    value if *i >= 0 && value < end => Ok(Value::Integer(value as i64)),
+                    _ => Err(VMError::IndexOutOfBounds {
+                        index: *i,
+                        length: length.try_into().unwrap_or(usize::MAX),
+                    }),
+                }
+            }
+            (Value::List(list), Value::Integer(i)) => {
+                let list = list.borrow();
+                usize::try_from(*i)
+                    .ok()
+                    .and_then(|i| list.get(i))
+                    .cloned()
+                    .ok_or(VMError::IndexOutOfBounds {
+                        index: *i,
+                        length: list.len(),
+                    })
+            }
+            _ => Err(VMError::TypeError {
+                op: "index",
+                lhs: self.variant_name(),
+                rhs: Some(index.variant_name()),
+            }),
+        }
+    }
+
+    /// Slices a list, or a string by characters. Like Python, negative
+    /// bounds count from the end, bounds past either end are clamped, and
+    /// a start after the end gives an empty slice.
+    pub fn slice(&self, start: &Value, end: &Value) -> VMResult<Value> {
+        let length = match self {
+            Value::List(list) => list.borrow().len(),
+            Value::String(string) => string.chars().count(),
             _ => {
-                return Err(ValueReport::TypeError(format!(
-                    "Cannot negate {}",
-                    self.variant_name()
-                ))
-                .make()
-                .into())
+                return Err(VMError::TypeError {
+                    op: "slice",
+                    lhs: self.variant_name(),
+                    rhs: None,
+                })
             }
+        };
+        let bound = |bound: &Value, default: usize| match bound {
+            Value::None => Ok(default),
+            Value::Integer(i) if *i < 0 => Ok(length.saturating_sub(i.unsigned_abs() as usize)),
+            Value::Integer(i) => Ok((*i as usize).min(length)),
+            _ => Err(VMError::TypeError {
+                op: "slice",
+                lhs: self.variant_name(),
+                rhs: Some(bound.variant_name()),
+            }),
+        };
+        let start = bound(start, 0)?;
+        let end = bound(end, length)?.max(start);
+        Ok(match self {
+            Value::List(list) => {
+                Value::List(Rc::new(RefCell::new(list.borrow()[start..end].to_vec())))
+            }
+            Value::String(string) => Value::String(
+                string
+                    .chars()
+                    .skip(start)
+                    .take(end - start)
+                    .collect::<String>()
+                    .into(),
+            ),
+            _ => unreachable!(),
         })
     }
+
+    /// Whether the value counts as true for `and`, `or` and `!`. `None`,
+    /// `False`, zero, and an empty string, list, map or range are false,
+    /// and everything else is true. The condition of an `if` or `while`
+    /// isn't tested this way, and has to be a boolean.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Integer(value) => *value != 0,
+            Value::Float(value) => *value != 0.0,
+            Value::Boolean(value) => *value,
+            Value::String(value) => !value.is_empty(),
+            Value::List(list) => !list.borrow().is_empty(),
+            Value::Map(map) => !map.borrow().is_empty(),
+            Value::Range { .. } => {
+                let (start, end) = self.range_bounds().unwrap();
+                end > start as i128
+            }
+            Value::None => false,
+        }
+    }
+
+    /// `!value`, which is true when the value is falsy.
+    pub fn not(&self) -> VMResult<Value> {
+        Ok(Value::Boolean(!self.is_truthy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::testing::{eval, run};
+
+    fn type_error(op: &'static str, lhs: &'static str, rhs: &'static str) -> VMError {
+        VMError::TypeError {
+            op,
+            lhs,
+            rhs: Some(rhs),
+        }
+    }
+
+    #[test]
+    fn shifts() {
+        assert_eq!(eval("return 1 << 10 >> 2"), Value::Integer(256));
+        // Looser than `+`, so the shift is by 3.
+        assert_eq!(eval("return 1 << 2 + 1"), Value::Integer(8));
+        // Arithmetic, keeping the sign.
+        assert_eq!(eval("return -8 >> 1"), Value::Integer(-4));
+        // Not confused with `<=` and `>=`.
+        assert_eq!(eval("return 1 << 2 >= 4"), Value::Boolean(true));
+        assert_eq!(run("return 1 << -1"), Err(VMError::InvalidShift(-1)));
+        assert_eq!(run("return 1 >> 64"), Err(VMError::InvalidShift(64)));
+        assert_eq!(
+            run("return 1.0 << 1"),
+            Err(type_error("shift", "Float", "Integer"))
+        );
+    }
+
+    #[test]
+    fn string_concatenation() {
+        assert_eq!(eval("return \"ab\" + \"cd\""), Value::String("abcd".into()));
+        assert_eq!(eval("return \"\" + \"\""), Value::String("".into()));
+        assert_eq!(eval("return \"a\" + \"\""), Value::String("a".into()));
+        // Anything else is converted to a string.
+        assert_eq!(eval("return \"n = \" + 1"), Value::String("n = 1".into()));
+        assert_eq!(eval("return None + \"!\""), Value::String("None!".into()));
+        assert_eq!(
+            run("return [] + 1"),
+            Err(type_error("add", "List", "Integer"))
+        );
+    }
+
+    #[test]
+    fn long_concatenation() {
+        let source = "let s = \"\"\nfor i in 0..10000 { s += \"ab\" }\nreturn s == \"ab\" * 10000";
+        assert_eq!(eval(source), Value::Boolean(true));
+        let source = "let s = \"x\"\nfor i in 0..20 { s = s + s }\nreturn s == \"x\" * (1 << 20)";
+        assert_eq!(eval(source), Value::Boolean(true));
+    }
+
+    #[test]
+    fn string_repetition() {
+        assert_eq!(eval("return \"ab\" * 3"), Value::String("ababab".into()));
+        assert_eq!(eval("return 3 * \"ab\""), Value::String("ababab".into()));
+        assert_eq!(eval("return \"ab\" * 0"), Value::String("".into()));
+        assert_eq!(eval("return \"ab\" * -2"), Value::String("".into()));
+        assert_eq!(
+            run("return \"ab\" * (1 << 62)"),
+            Err(VMError::StringTooLong {
+                length: 1 << 63,
+                limit: 1 << 30,
+            })
+        );
+        assert_eq!(
+            run("return \"ab\" * 1.5"),
+            Err(type_error("multiply", "String", "Float"))
+        );
+        assert_eq!(
+            run("return \"ab\" * \"c\""),
+            Err(type_error("multiply", "String", "String"))
+        );
+    }
+
+    #[test]
+    fn none() {
+        assert_eq!(eval("return None == None"), Value::Boolean(true));
+        assert_eq!(eval("return None == 0"), Value::Boolean(false));
+        assert_eq!(eval("return None != False"), Value::Boolean(true));
+        assert_eq!(
+            run("return None + 1"),
+            Err(type_error("add", "None", "Integer"))
+        );
+        assert_eq!(
+            run("return -None"),
+            Err(VMError::TypeError {
+                op: "negate",
+                lhs: "None",
+                rhs: None
+            })
+        );
+        assert_eq!(Value::None.equals(&Value::None), Ok(Value::Boolean(true)));
+        assert_eq!(
+            Value::None.equals(&Value::Integer(0)),
+            Ok(Value::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(eval("return 1..10").to_string(), "1..10");
+        assert_eq!(eval("return 3 in 1..=3"), Value::Boolean(true));
+        assert_eq!(eval("return 3 in 1..3"), Value::Boolean(false));
+        assert_eq!(
+            eval("return [(1..10).len, (1..=10).len, (4..4).len]").to_string(),
+            "[9, 10, 0]"
+        );
+        assert_eq!(
+            eval("let r = 2..5\nreturn [r.start, r.end, r[1]]").to_string(),
+            "[2, 5, 3]"
+        );
+        let source = "let total = 0\nlet r = 1..=4\nfor i in r { total += i }\nreturn total";
+        assert_eq!(eval(source), Value::Integer(10));
+        assert_eq!(
+            run("let a = 3\nreturn a..1"),
+            Err(VMError::ReversedRange { start: 3, end: 1 })
+        );
+        assert_eq!(
+            run("let a = 1.5\nreturn a..3"),
+            Err(type_error("make a range from", "Float", "Integer"))
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(eval("return [1, 2] == [1, 2]"), Value::Boolean(true));
+        assert_eq!(eval("return [1, 2] == [2, 1]"), Value::Boolean(false));
+        assert_eq!(eval("return [1] == [1, 1]"), Value::Boolean(false));
+        assert_eq!(eval("return [[1], []] == [[1], []]"), Value::Boolean(true));
+        assert_eq!(eval("return [1] == [1.0]"), Value::Boolean(false));
+        assert_eq!(
+            eval("return [1, \"a\", None, [2.5]]").to_string(),
+            "[1, \"a\", None, [2.5]]"
+        );
+        assert_eq!(
+            eval("return [[1], [2, [3]]]").to_string(),
+            "[[1], [2, [3]]]"
+        );
+        assert_eq!(
+            eval("let xs = [[1], [2, 3]]\nreturn xs[1][0]"),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            run("return [1, 2][-1]"),
+            Err(VMError::IndexOutOfBounds {
+                index: -1,
+                length: 2
+            })
+        );
+    }
+
+    #[test]
+    fn slicing_multibyte_strings() {
+        // By characters, so none is split.
+        assert_eq!(eval("return \"héllo\"[1:3]"), Value::String("él".into()));
+        assert_eq!(eval("return \"日本語\"[:2]"), Value::String("日本".into()));
+        assert_eq!(eval("return \"日本語\"[-1:]"), Value::String("語".into()));
+        assert_eq!(eval("return \"a🎉b\"[1:2]"), Value::String("🎉".into()));
+        assert_eq!(eval("return \"日本語\"[2:10]"), Value::String("語".into()));
+        assert_eq!(eval("return \"日本語\"[2:1]"), Value::String("".into()));
+    }
 }
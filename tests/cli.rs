@@ -0,0 +1,187 @@
+//! Runs the moonlite binary the way a user would, for the behaviour that
+//! only shows at the process level: flags, exit codes and what's printed.
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Writes `source` to a file of its own, returning the path.
+fn script(name: &str, source: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("moonlite-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn moonlite() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_moonlite"))
+}
+
+/// Reports are colored even when they aren't going to a terminal.
+fn strip_colors(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn stderr(output: &Output) -> String {
+    strip_colors(&output.stderr)
+}
+
+#[test]
+fn time_reports_every_statistic() {
+    let path = script("time.ml", "let x = 1\nreturn x + 2\n");
+    let output = moonlite().arg("--time").arg(&path).output().unwrap();
+    assert!(output.status.success());
+    let stderr = stderr(&output);
+    let field = |name: &str| {
+        stderr
+            .lines()
+            .find_map(|line| line.trim_start().strip_prefix(name))
+            .unwrap_or_else(|| panic!("No {name} in\n{stderr}"))
+            .trim()
+            .to_string()
+    };
+    for phase in ["parse", "compile", "run"] {
+        let time = field(phase);
+        assert!(
+            time.strip_suffix("ms").unwrap().parse::<f64>().is_ok(),
+            "{time}"
+        );
+    }
+    let rss = field("peak rss");
+    assert!(rss == "unavailable" || rss.strip_suffix(" kB").unwrap().parse::<usize>().is_ok());
+    assert!(field("ast nodes").parse::<usize>().is_ok());
+    let chunk = field("chunk size");
+    assert!(chunk
+        .strip_suffix(" bytes")
+        .unwrap()
+        .parse::<usize>()
+        .is_ok());
+    let pool = field("constant pool");
+    let (count, bytes) = pool.split_once(" (").unwrap();
+    assert!(count.parse::<usize>().is_ok());
+    assert!(bytes
+        .strip_suffix(" bytes)")
+        .unwrap()
+        .parse::<usize>()
+        .is_ok());
+}
+
+#[test]
+fn internal_panic_is_reported_as_a_bug() {
+    let path = script("ice.ml", "return 1\n");
+    for phase in ["parsing", "compilation", "execution"] {
+        let output = moonlite()
+            .env("MOONLITE_FORCE_ICE", phase)
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(101));
+        let stderr = stderr(&output);
+        assert!(
+            stderr.starts_with(&format!(
+                "Bug: Internal compiler error during {phase} of {path:?}"
+            )),
+            "{stderr}"
+        );
+        assert!(stderr.contains("Note: panicked with 'Forced by MOONLITE_FORCE_ICE'"));
+        assert!(stderr.contains("Help: This is a bug in moonlite."));
+    }
+}
+
+#[test]
+fn ordinary_errors_are_not_internal() {
+    let path = script("syntax.ml", "let = 1\n");
+    let output = moonlite().arg(&path).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!stderr(&output).contains("Internal compiler error"));
+}
+
+#[test]
+fn broken_pipe_exits_quietly() {
+    let source = "let x = 1\n".repeat(10_000) + "return x\n";
+    let path = script("pipe.ml", &source);
+    let mut child = moonlite()
+        .arg("--show-bytecode")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Like piping into `head -c1`, which exits after the first byte.
+    let mut stdout = child.stdout.take().unwrap();
+    stdout.read_exact(&mut [0]).unwrap();
+    drop(stdout);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(141));
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+#[cfg(unix)]
+fn interrupt_cancels_the_program() {
+    let path = script("interrupt.ml", "loop {}\n");
+    let child = moonlite()
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Give it time to install the handler and start looping.
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("cancelled"), "{}", stderr(&output));
+}
+
+#[test]
+fn trace_statements() {
+    let path = script(
+        "trace.ml",
+        "let i = 0\nwhile i < 2 {\n    i = i + 1\n}\nreturn i\n",
+    );
+    let output = moonlite()
+        .args(["--trace", "statements"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let path = path.display();
+    let expected = [
+        format!("{path}:1:1 | let i = 0"),
+        format!("{path}:2:1 | while i < 2 {{"),
+        format!("{path}:3:5 | i = i + 1"),
+        format!("{path}:2:1 | while i < 2 {{"),
+        format!("{path}:3:5 | i = i + 1"),
+        format!("{path}:2:1 | while i < 2 {{"),
+        format!("{path}:5:1 | return i"),
+    ];
+    assert_eq!(stderr(&output).lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn help() {
+    let output = moonlite().arg("--help").output().unwrap();
+    assert!(output.status.success());
+    let stdout = strip_colors(&output.stdout);
+    let usage = stdout.lines().nth(1).unwrap();
+    assert!(usage.starts_with("moonlite "), "{usage}");
+    assert!(stdout.contains("OPTIONS"));
+    let output = moonlite().arg("-h").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(strip_colors(&output.stdout), format!("Usage: {usage}\n"));
+}